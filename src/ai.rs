@@ -0,0 +1,340 @@
+//! Computer-controlled snakes.
+//!
+//! On every move the autopilot looks over the board the way a player would, through a
+//! `Board` of free and blocked cells, and picks a direction. The driver hands that to
+//! `handle_input` as a `Command::Steer`, exactly like a turn typed in by a player, so
+//! opponents need nothing recorded to replay the same way.
+
+use crate::level::Level;
+use crate::render::{BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::settings::GameSettings;
+use crate::snake::{Cell, Dir, Player};
+
+/// How a computer snake weighs the apple against staying alive.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Skill {
+    /// Takes the shortest path to the apple, swerving only to avoid an immediate crash.
+    Greedy,
+    /// Goes for the apple, but not into pockets too small to hold it, nor onto cells
+    /// another snake could reach on the same move.
+    Cautious,
+    /// Heads wherever leaves it the most room, and only then toward the apple.
+    Survivor
+}
+
+impl Skill {
+    pub const ALL: [Skill; 3] = [Skill::Greedy, Skill::Cautious, Skill::Survivor];
+
+    /// Position in `ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Skill::Greedy => "greedy",
+            Skill::Cautious => "cautious",
+            Skill::Survivor => "survivor"
+        }
+    }
+
+    pub(crate) fn cycle(self, up: bool) -> Self {
+        let n = Self::ALL.len();
+        Self::ALL[(self.index() + if up { 1 } else { n - 1 }) % n]
+    }
+}
+
+/// Marks a cell no search got to.
+const UNREACHED: u16 = u16::MAX;
+
+/// Which cells a snake could move into on the next move.
+pub struct Board<'a> {
+    rules: &'a GameSettings,
+    blocked: [u128; BUFFER_HEIGHT],
+    /// Whether the level has walls of its own.
+    walled: bool
+}
+
+/// How many moves every reachable cell is from where a search started.
+struct Distances {
+    steps: [[u16; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    reached: usize
+}
+
+impl Distances {
+    fn to(&self, cell: Cell) -> u16 {
+        self.steps[cell.row()][cell.col()]
+    }
+}
+
+impl<'a> Board<'a> {
+    /// Level walls and the bodies of every snake still in the game. A tail that is
+    /// about to move out of the way doesn't count.
+    pub fn new(rules: &'a GameSettings, level: &Level, snakes: &[Player]) -> Self {
+        let mut board = Self { rules, blocked: [0; BUFFER_HEIGHT], walled: false };
+        for cell in level.walls(rules) {
+            board.block(cell);
+            board.walled = true;
+        }
+        for snake in snakes.iter().filter(|s| s.alive) {
+            let solid = if snake.is_growing() { snake.body.len() } else { snake.body.len() - 1 };
+            for cell in snake.body.iter().take(solid) {
+                board.block(cell);
+            }
+        }
+        board
+    }
+
+    /// Marks `cell` as somewhere not to go.
+    pub fn block(&mut self, cell: Cell) {
+        self.blocked[cell.row()] |= 1 << cell.col();
+    }
+
+    pub fn is_free(&self, cell: Cell) -> bool {
+        self.blocked[cell.row()] & 1 << cell.col() == 0
+    }
+
+    /// Where moving from `cell` along `dir` leads, if that is somewhere free.
+    fn step(&self, cell: Cell, dir: Dir) -> Option<Cell> {
+        self.rules.next_cell(cell, dir).filter(|&next| self.is_free(next))
+    }
+
+    /// A breadth-first search over the free cells, starting from `from`.
+    fn distances(&self, from: Cell) -> Distances {
+        let mut found = Distances { steps: [[UNREACHED; BUFFER_WIDTH]; BUFFER_HEIGHT], reached: 1 };
+        let mut queue = [Cell::default(); BUFFER_WIDTH * BUFFER_HEIGHT];
+        found.steps[from.row()][from.col()] = 0;
+        queue[0] = from;
+        let mut next = 0;
+        while next < found.reached {
+            let cell = queue[next];
+            next += 1;
+            for dir in Dir::ALL {
+                if let Some(n) = self.step(cell, dir) {
+                    if found.to(n) == UNREACHED {
+                        found.steps[n.row()][n.col()] = found.to(cell) + 1;
+                        queue[found.reached] = n;
+                        found.reached += 1;
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+/// One way a snake could go, and what lies that way.
+struct Move {
+    dir: Dir,
+    /// Moves from there to the apple, or `UNREACHED`.
+    apple: u16,
+    /// Free cells reachable from there.
+    room: usize,
+    /// Whether another snake's head could get there on the same move.
+    contested: bool
+}
+
+impl Move {
+    /// How much `skill` likes this move for a snake `length` long. Higher is better,
+    /// compared field by field.
+    fn rank(&self, skill: Skill, length: usize) -> (bool, usize, usize) {
+        let closer = (UNREACHED - self.apple) as usize;
+        match skill {
+            Skill::Greedy => (true, closer, 0),
+            Skill::Cautious => (self.room >= length && !self.contested, closer, self.room),
+            Skill::Survivor => (true, self.room, closer)
+        }
+    }
+}
+
+/// Which way `snakes[me]` should head this move, or `None` if it is boxed in and
+/// will crash whatever it does. Ties go to carrying straight on.
+pub fn steer(skill: Skill, board: &Board, snakes: &[Player], me: usize, apple: Option<Cell>) -> Option<Dir> {
+    let snake = &snakes[me];
+    let head = snake.body.head();
+    let to_apple = apple.map(|apple| board.distances(apple));
+    let turns = Dir::ALL.into_iter().filter(|&d| d != snake.dir && d != snake.dir.opposite());
+    let mut best: Option<(Dir, (bool, usize, usize))> = None;
+    for dir in [snake.dir].into_iter().chain(turns) {
+        let Some(cell) = board.step(head, dir) else { continue };
+        let option = Move {
+            dir,
+            apple: to_apple.as_ref().map_or(UNREACHED, |d| d.to(cell)),
+            room: board.distances(cell).reached,
+            contested: snakes.iter().enumerate().any(|(i, other)| {
+                i != me && other.alive && Dir::ALL.iter().any(|&d| board.rules.next_cell(other.body.head(), d) == Some(cell))
+            })
+        };
+        let rank = option.rank(skill, snake.body.len());
+        if best.is_none_or(|(_, best)| rank > best) {
+            best = Some((option.dir, rank));
+        }
+    }
+    best.map(|(dir, _)| dir)
+}
+
+/// A closed path through every playfield cell, which a playfield with an even side
+/// always has. Row 0 runs left to right, the rows below it go back and forth over
+/// every column but the first, and the first column leads back up to the start. A
+/// playfield with an odd number of rows is walked the same way with rows and
+/// columns swapped.
+struct Cycle {
+    origin: Cell,
+    /// The playfield's size along and across the rows the cycle sweeps.
+    w: usize,
+    h: usize,
+    transposed: bool
+}
+
+impl Cycle {
+    fn new(rules: &GameSettings) -> Option<Self> {
+        let (w, h) = rules.playfield_size();
+        let origin = rules.playfield_cell(0);
+        if h % 2 == 0 {
+            Some(Self { origin, w, h, transposed: false })
+        } else if w % 2 == 0 {
+            Some(Self { origin, w: h, h: w, transposed: true })
+        } else {
+            None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.w * self.h
+    }
+
+    /// How far along the cycle `cell` is.
+    fn index(&self, cell: Cell) -> usize {
+        let (x, y) = (cell.col() - self.origin.col(), cell.row() - self.origin.row());
+        let (x, y) = if self.transposed { (y, x) } else { (x, y) };
+        let (w, h) = (self.w, self.h);
+        match (x, y) {
+            (x, 0) => x,
+            (0, y) => w + (h - 1) * (w - 1) + (h - 1 - y),
+            (x, y) if y % 2 == 1 => w + (y - 1) * (w - 1) + (w - 1 - x),
+            (x, y) => w + (y - 1) * (w - 1) + (x - 1)
+        }
+    }
+
+    /// Steps forward along the cycle from `from` to `to`.
+    fn ahead(&self, from: Cell, to: Cell) -> usize {
+        (self.index(to) + self.len() - self.index(from)) % self.len()
+    }
+}
+
+/// Steers `snakes[me]` round a `Cycle`, cutting across toward the apple while it is
+/// short enough for that to be safe, for a snake that grows `growth` per apple.
+///
+/// The body always lies along the cycle in order from tail to head, and the head only
+/// ever moves into the free stretch between them, so the snake can't trap itself and
+/// lasts until the board is nearly full. Playfields without a cycle, or with level
+/// walls in the way, fall back to `Skill::Survivor`, as does a snake whose way round
+/// is blocked by anything else.
+pub fn autopilot(board: &Board, snakes: &[Player], me: usize, growth: usize, apple: Option<Cell>) -> Option<Dir> {
+    let Some(cycle) = Cycle::new(board.rules).filter(|_| !board.walled) else {
+        return steer(Skill::Survivor, board, snakes, me, apple);
+    };
+    let snake = &snakes[me];
+    let n = cycle.len();
+    let head = snake.body.head();
+    let gap = if snake.body.len() == 1 { n } else { cycle.ahead(head, snake.body.tail()) };
+    let target = apple.map_or(1, |apple| cycle.ahead(head, apple));
+    let cutting = snake.body.len() + snake.growth + growth < n / 2;
+    // Prefer the furthest move that doesn't overshoot the apple, then the nearest.
+    let mut best: Option<(Dir, (bool, usize))> = None;
+    for dir in Dir::ALL.into_iter().filter(|&d| d != snake.dir.opposite()) {
+        let Some(next) = board.step(head, dir) else { continue };
+        let d = cycle.ahead(head, next);
+        if d != 1 && !(cutting && d < gap && gap - d > snake.growth + growth) {
+            continue;
+        }
+        let rank = if d <= target { (true, d) } else { (false, n - d) };
+        if best.is_none_or(|(_, best)| rank > best) {
+            best = Some((dir, rank));
+        }
+    }
+    best.map(|(dir, _)| dir).or_else(|| steer(Skill::Survivor, board, snakes, me, apple))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dead end four cells deep hanging off the top two rows.
+    const POCKET: &str = "; Pocket\n\
+                          ..................\n\
+                          ..................\n\
+                          #######.##########\n\
+                          ......#.#.........\n\
+                          ......#.#.........\n\
+                          ......#.#.........\n\
+                          ......###.........\n\
+                          ..>...............\n";
+
+    #[test]
+    fn skills_differ_over_an_apple_in_a_dead_end() {
+        let level = Level::parse(POCKET).unwrap();
+        let rules = level.fit(GameSettings::default());
+        // Six long, heading right along row 1 and level with the pocket's mouth.
+        let at = |col: usize, row: usize| Cell::new(col + 1, row + 2);
+        let mut snake = Player::new(at(2, 1), Dir::Right, 0);
+        (3..=7).for_each(|col| snake.body.push_head(at(col, 1)));
+        let snakes = [snake];
+        let board = Board::new(&rules, &level, &snakes);
+        let apple = Some(at(7, 5));
+        assert_eq!(steer(Skill::Greedy, &board, &snakes, 0, apple), Some(Dir::Down));
+        assert_eq!(steer(Skill::Cautious, &board, &snakes, 0, apple), Some(Dir::Right));
+        assert_eq!(steer(Skill::Survivor, &board, &snakes, 0, apple), Some(Dir::Right));
+    }
+
+    #[test]
+    fn cautious_keeps_clear_of_other_heads() {
+        let level = Level::open();
+        let rules = GameSettings { width: 30, height: 12, ..GameSettings::default() };
+        let snakes = [Player::new(Cell::new(5, 5), Dir::Right, 0), Player::new(Cell::new(6, 4), Dir::Left, 0)];
+        let board = Board::new(&rules, &level, &snakes);
+        let apple = Some(Cell::new(7, 5));
+        assert_eq!(steer(Skill::Greedy, &board, &snakes, 0, apple), Some(Dir::Right));
+        assert_eq!(steer(Skill::Cautious, &board, &snakes, 0, apple), Some(Dir::Down));
+    }
+
+    #[test]
+    fn boxed_in_snakes_give_up() {
+        let rules = GameSettings { width: 30, height: 12, ..GameSettings::default() };
+        let mut wall = Player::new(Cell::new(2, 2), Dir::Down, 5);
+        wall.body.push_head(Cell::new(2, 3));
+        let snakes = [wall, Player::new(Cell::new(1, 2), Dir::Up, 0)];
+        let board = Board::new(&rules, &Level::open(), &snakes);
+        assert_eq!(steer(Skill::Survivor, &board, &snakes, 1, None), None);
+    }
+
+    #[test]
+    fn autopilot_fills_most_of_a_small_field() {
+        let rules = GameSettings { width: 12, height: 8, growth: 3, ..GameSettings::default() };
+        let level = Level::open();
+        let (w, h) = rules.playfield_size();
+        let n = w * h;
+        let mut snake = [Player::new(rules.playfield_cell(20), Dir::Right, 2)];
+        let mut apple = 37;
+        for _ in 0..5000 {
+            let me = &snake[0];
+            if me.body.len() + 3 > n * 3 / 4 {
+                return;
+            }
+            while me.body.contains(rules.playfield_cell(apple)) {
+                apple = (apple + 29) % n;
+            }
+            let board = Board::new(&rules, &level, &snake);
+            let dir = autopilot(&board, &snake, 0, 3, Some(rules.playfield_cell(apple))).expect("boxed in");
+            let next = board.step(me.body.head(), dir).expect("crashed");
+            let me = &mut snake[0];
+            me.dir = dir;
+            me.body.push_head(next);
+            if me.is_growing() { me.growth -= 1 } else { me.body.pop_tail(); }
+            if next == rules.playfield_cell(apple) {
+                me.growth += 3;
+            }
+        }
+        panic!("only grew to {}", snake[0].body.len());
+    }
+}
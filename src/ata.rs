@@ -0,0 +1,171 @@
+//! A polling ATA PIO driver for one drive, enough to read and write single sectors.
+//!
+//! Every wait on the drive is bounded, so a missing or wedged drive costs a few
+//! milliseconds and an error rather than a hang. Only meaningful in the kernel: port
+//! I/O faults in a hosted process.
+
+use x86_64::instructions::port::Port;
+
+use crate::disk::{BlockDevice, DiskError, SECTOR_SIZE};
+
+const PRIMARY_BASE: u16 = 0x1F0;
+const PRIMARY_CONTROL: u16 = 0x3F6;
+
+// Register offsets from the base port.
+const DATA: u16 = 0;
+const SECTOR_COUNT: u16 = 2;
+const LBA_LOW: u16 = 3;
+const LBA_MID: u16 = 4;
+const LBA_HIGH: u16 = 5;
+const DRIVE: u16 = 6;
+const STATUS: u16 = 7;
+const COMMAND: u16 = 7;
+
+const STATUS_ERR: u8 = 0x01;
+const STATUS_DRQ: u8 = 0x08;
+const STATUS_FAULT: u8 = 0x20;
+const STATUS_BUSY: u8 = 0x80;
+
+const CMD_READ: u8 = 0x20;
+const CMD_WRITE: u8 = 0x30;
+const CMD_FLUSH: u8 = 0xE7;
+const CMD_IDENTIFY: u8 = 0xEC;
+
+/// Masks the drive's interrupt; we poll instead.
+const CONTROL_NO_IRQ: u8 = 0x02;
+
+/// Status reads before giving up. A port read takes around a microsecond on real
+/// hardware, so no single wait lasts much past 50ms.
+const SPIN_LIMIT: u32 = 50_000;
+
+pub struct Ata {
+    base: u16,
+    slave: bool,
+    sectors: u32
+}
+
+impl Ata {
+    /// The second drive on the primary bus. Under QEMU that is `-drive
+    /// file=...,format=raw,index=1`, next to the boot image on index 0.
+    pub fn primary_slave() -> Result<Self, DiskError> {
+        Self::identify(PRIMARY_BASE, PRIMARY_CONTROL, true)
+    }
+
+    fn identify(base: u16, control: u16, slave: bool) -> Result<Self, DiskError> {
+        let mut ata = Self { base, slave, sectors: 0 };
+        unsafe { Port::<u8>::new(control).write(CONTROL_NO_IRQ) };
+        // Nothing attached to the bus reads as all ones.
+        if ata.read(STATUS) == 0xFF {
+            return Err(DiskError::NoDevice);
+        }
+        ata.select(0xA0);
+        for register in [SECTOR_COUNT, LBA_LOW, LBA_MID, LBA_HIGH] {
+            ata.write(register, 0);
+        }
+        ata.write(COMMAND, CMD_IDENTIFY);
+        if ata.read(STATUS) == 0 {
+            return Err(DiskError::NoDevice);
+        }
+        ata.wait_not_busy()?;
+        // ATAPI and SATA devices answer with a signature here instead.
+        if ata.read(LBA_MID) != 0 || ata.read(LBA_HIGH) != 0 {
+            return Err(DiskError::NoDevice);
+        }
+        ata.wait_for_data()?;
+        let mut words = [0u16; SECTOR_SIZE / 2];
+        let mut data: Port<u16> = Port::new(base + DATA);
+        for word in words.iter_mut() {
+            *word = unsafe { data.read() };
+        }
+        // Words 60 and 61 hold the number of 28-bit addressable sectors.
+        ata.sectors = words[60] as u32 | (words[61] as u32) << 16;
+        if ata.sectors == 0 {
+            return Err(DiskError::NoDevice);
+        }
+        Ok(ata)
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        unsafe { Port::<u8>::new(self.base + register).read() }
+    }
+
+    fn write(&self, register: u16, value: u8) {
+        unsafe { Port::<u8>::new(self.base + register).write(value) }
+    }
+
+    /// Picks our drive on the bus, then gives it the 400ns the spec asks for to put
+    /// its status up.
+    fn select(&self, bits: u8) {
+        self.write(DRIVE, bits | (self.slave as u8) << 4);
+        for _ in 0..4 {
+            self.read(STATUS);
+        }
+    }
+
+    fn wait_not_busy(&self) -> Result<u8, DiskError> {
+        for _ in 0..SPIN_LIMIT {
+            let status = self.read(STATUS);
+            if status & STATUS_BUSY == 0 {
+                return Ok(status);
+            }
+        }
+        Err(DiskError::Timeout)
+    }
+
+    fn wait_for_data(&self) -> Result<(), DiskError> {
+        for _ in 0..SPIN_LIMIT {
+            let status = self.read(STATUS);
+            if status & (STATUS_ERR | STATUS_FAULT) != 0 {
+                return Err(DiskError::DeviceError);
+            }
+            if status & (STATUS_BUSY | STATUS_DRQ) == STATUS_DRQ {
+                return Ok(());
+            }
+        }
+        Err(DiskError::Timeout)
+    }
+
+    /// Issues a one-sector `command` at `lba` using 28-bit addressing.
+    fn start(&self, lba: u32, command: u8) -> Result<(), DiskError> {
+        if lba >= self.sectors {
+            return Err(DiskError::Full);
+        }
+        self.wait_not_busy()?;
+        self.select(0xE0 | (lba >> 24) as u8 & 0x0F);
+        self.write(SECTOR_COUNT, 1);
+        self.write(LBA_LOW, lba as u8);
+        self.write(LBA_MID, (lba >> 8) as u8);
+        self.write(LBA_HIGH, (lba >> 16) as u8);
+        self.write(COMMAND, command);
+        self.wait_for_data()
+    }
+}
+
+impl BlockDevice for Ata {
+    fn sectors(&self) -> u32 {
+        self.sectors
+    }
+
+    fn read_sector(&mut self, lba: u32, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), DiskError> {
+        self.start(lba, CMD_READ)?;
+        let mut data: Port<u16> = Port::new(self.base + DATA);
+        for pair in buf.chunks_exact_mut(2) {
+            pair.copy_from_slice(&unsafe { data.read() }.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    /// Writes through the drive's cache, so the sector is on the medium once this returns.
+    fn write_sector(&mut self, lba: u32, buf: &[u8; SECTOR_SIZE]) -> Result<(), DiskError> {
+        self.start(lba, CMD_WRITE)?;
+        let mut data: Port<u16> = Port::new(self.base + DATA);
+        for pair in buf.chunks_exact(2) {
+            unsafe { data.write(u16::from_le_bytes([pair[0], pair[1]])) };
+        }
+        self.write(COMMAND, CMD_FLUSH);
+        if self.wait_not_busy()? & (STATUS_ERR | STATUS_FAULT) != 0 {
+            return Err(DiskError::DeviceError);
+        }
+        Ok(())
+    }
+}
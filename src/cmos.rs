@@ -0,0 +1,42 @@
+use x86_64::instructions::port::Port;
+
+const ADDRESS_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+
+/// Setting the top bit of the address byte keeps NMIs masked while we talk to the chip.
+const NMI_DISABLE: u8 = 0x80;
+
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_STATUS_A: u8 = 0x0A;
+
+/// Reads one byte of CMOS RAM.
+///
+/// Only meaningful in the kernel: port I/O faults in a hosted process.
+pub fn read(register: u8) -> u8 {
+    let mut address: Port<u8> = Port::new(ADDRESS_PORT);
+    let mut data: Port<u8> = Port::new(DATA_PORT);
+    unsafe {
+        address.write(NMI_DISABLE | register);
+        data.read()
+    }
+}
+
+/// Writes one byte of CMOS RAM. Only the spare bytes `persist` owns should ever be
+/// written: the rest belongs to the clock and the firmware.
+pub fn write(register: u8, value: u8) {
+    let mut address: Port<u8> = Port::new(ADDRESS_PORT);
+    let mut data: Port<u8> = Port::new(DATA_PORT);
+    unsafe {
+        address.write(NMI_DISABLE | register);
+        data.write(value);
+    }
+}
+
+/// The RTC's time of day as raw register bytes packed into `0x00HHMMSS`. Waits out
+/// any update in progress so the three fields agree with each other.
+pub fn rtc_time() -> u32 {
+    while read(RTC_STATUS_A) & 0x80 != 0 {}
+    (read(RTC_HOURS) as u32) << 16 | (read(RTC_MINUTES) as u32) << 8 | read(RTC_SECONDS) as u32
+}
@@ -0,0 +1,104 @@
+//! The moments between a player's crash and the results: the head flashes, the body
+//! dissolves from head to tail, then a banner goes up. Timed in timer ticks.
+
+use crate::render::{Color, ColorCode, Renderer, BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::settings::MAX_PLAYERS;
+use crate::snake::Player;
+use crate::ui::{draw_box, draw_str, Align, Frame, Rect};
+use crate::{Pal, Sym};
+
+const FLASH_TICKS: usize = 12;
+/// The longest a body takes to dissolve; longer snakes lose several segments a tick.
+const DISSOLVE_TICKS: usize = 36;
+const BANNER_TICKS: usize = 27;
+/// What a dissolving segment shows on each tick after it starts to go.
+const FADE: [(char, Color); 3] = [('*', Color::Yellow), ('+', Color::LightRed), ('.', Color::DarkGray)];
+
+/// How far the sequence has got.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct DeathScene {
+    tick: usize,
+    /// Which players crashed.
+    crashed: [bool; MAX_PLAYERS as usize],
+    /// Segments to dissolve each tick.
+    per_tick: usize,
+    dissolve: usize
+}
+
+impl DeathScene {
+    pub fn new(players: &[Player], crashed: [bool; MAX_PLAYERS as usize]) -> Self {
+        let longest = players.iter().zip(crashed).filter(|(_, c)| *c).map(|(p, _)| p.body.len()).max().unwrap_or(1);
+        let per_tick = longest.div_ceil(DISSOLVE_TICKS);
+        Self { tick: 0, crashed, per_tick, dissolve: longest.div_ceil(per_tick) + FADE.len() }
+    }
+
+    /// Draws the next tick of the sequence. Returns false once it is over.
+    pub fn advance<R: Renderer>(&mut self, players: &[Player], r: &mut R) -> bool {
+        let tick = self.tick;
+        self.tick += 1;
+        let crashed = players.iter().zip(self.crashed).enumerate().filter(|(_, (_, c))| *c);
+        if tick < FLASH_TICKS {
+            for (p, (player, _)) in crashed {
+                let head = player.body.head();
+                let color = if tick & 2 == 0 {
+                    ColorCode::new(Color::White, Color::Red)
+                } else {
+                    Pal::disp(if p == 0 { Pal::Snake } else { Pal::Snake2 })
+                };
+                r.plot(Sym::disp(Sym::Head), head.col(), head.row(), color);
+            }
+            return true;
+        }
+        let tick = tick - FLASH_TICKS;
+        if tick < self.dissolve {
+            for (_, (player, _)) in crashed {
+                for (i, cell) in player.body.iter().enumerate() {
+                    let Some(stage) = tick.checked_sub(i / self.per_tick) else { break };
+                    match FADE.get(stage) {
+                        Some(&(c, color)) => r.plot(c, cell.col(), cell.row(), ColorCode::new(color, Color::Black)),
+                        None if stage == FADE.len() => r.plot(Sym::disp(Sym::Empty), cell.col(), cell.row(), Pal::disp(Pal::EmptySpace)),
+                        None => {}
+                    }
+                }
+            }
+            return true;
+        }
+        let tick = tick - self.dissolve;
+        if tick == 0 {
+            let text = Pal::disp(Pal::Text);
+            let banner = Rect::new(BUFFER_WIDTH / 2 - 8, BUFFER_HEIGHT / 2 - 1, 16, 3);
+            draw_box(r, banner, Frame::Double, text);
+            draw_str(r, "GAME OVER", banner.middle(), banner.top + 1, Align::Center, text);
+        }
+        tick + 1 < BANNER_TICKS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Grid;
+    use crate::snake::{Cell, Dir};
+
+    #[test]
+    fn head_flashes_then_the_body_goes_head_first() {
+        let mut player = Player::new(Cell::new(10, 5), Dir::Right, 0);
+        (11..14).for_each(|col| player.body.push_head(Cell::new(col, 5)));
+        let players = [player];
+        let mut scene = DeathScene::new(&players, [true, false]);
+        let mut grid = Grid::default();
+        scene.advance(&players, &mut grid);
+        assert_eq!(grid.peek(13, 5), ('0', ColorCode::new(Color::White, Color::Red)));
+        for _ in 1..FLASH_TICKS + 2 {
+            scene.advance(&players, &mut grid);
+        }
+        assert_eq!((grid.char_at(13, 5), grid.char_at(12, 5), grid.char_at(11, 5)), ('+', '*', ' '));
+        let mut ticks = FLASH_TICKS + 2;
+        while scene.advance(&players, &mut grid) {
+            ticks += 1;
+        }
+        assert_eq!(ticks + 1, FLASH_TICKS + 4 + FADE.len() + BANNER_TICKS);
+        assert!((10..14).all(|col| grid.char_at(col, 5) == ' '));
+        assert!(grid.reads(35, 12, "GAME OVER"));
+    }
+}
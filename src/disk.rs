@@ -0,0 +1,544 @@
+//! An append-only log of finished games on a raw disk, so the full score history and
+//! the replays survive reboots.
+//!
+//! Sector 0 holds a header naming the format and saying where the log ends. Each game
+//! follows as one record starting on a sector boundary: the bytes `GAME`, the payload
+//! length and a CRC-32 of the payload, then the payload itself (seed, settings, score,
+//! initials, whether the recording is complete, and the tick-stamped commands).
+//!
+//! A record only counts once the header has been rewritten past it, so a game cut off
+//! halfway through saving is simply missing after a reboot.
+
+use core::fmt;
+
+use crate::input::Command;
+use crate::render::Renderer;
+use crate::replay::{Recording, MAX_EVENTS};
+use crate::scores::INITIALS;
+use crate::settings::GameSettings;
+use crate::SnakeDriver;
+
+pub const SECTOR_SIZE: usize = 512;
+
+/// Something that reads and writes whole sectors.
+pub trait BlockDevice {
+    /// Number of addressable sectors.
+    fn sectors(&self) -> u32;
+    fn read_sector(&mut self, lba: u32, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), DiskError>;
+    fn write_sector(&mut self, lba: u32, buf: &[u8; SECTOR_SIZE]) -> Result<(), DiskError>;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DiskError {
+    NoDevice,
+    /// The drive stayed busy for longer than we are willing to wait.
+    Timeout,
+    /// The drive reported an error.
+    DeviceError,
+    /// No log header in sector 0.
+    Unformatted,
+    /// The header or a record fails its checks.
+    Corrupt,
+    Full
+}
+
+impl DiskError {
+    /// A short description that fits on the title screen.
+    pub fn message(&self) -> &'static str {
+        match self {
+            DiskError::NoDevice => "no disk",
+            DiskError::Timeout => "drive not responding",
+            DiskError::DeviceError => "drive error",
+            DiskError::Unformatted => "not formatted",
+            DiskError::Corrupt => "log damaged",
+            DiskError::Full => "disk full"
+        }
+    }
+}
+
+impl fmt::Display for DiskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DiskError {}
+
+/// How the save disk looks to the player.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DiskStatus {
+    Missing,
+    Ready { games: u32 },
+    Failed(DiskError)
+}
+
+impl DiskStatus {
+    /// Whether formatting could bring the disk into use.
+    pub fn can_format(&self) -> bool {
+        matches!(self, DiskStatus::Failed(DiskError::Unformatted | DiskError::Corrupt))
+    }
+}
+
+/// One finished game as it is kept on disk. `initials` are all zero if none were
+/// entered.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameRecord {
+    pub recording: Recording,
+    pub score: u16,
+    pub initials: [u8; INITIALS]
+}
+
+const HEADER_MAGIC: &[u8; 8] = b"SNAKELOG";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 22;
+const RECORD_MAGIC: &[u8; 4] = b"GAME";
+const RECORD_HEADER_LEN: usize = 12;
+const EVENT_LEN: usize = 5;
+const GAME_FIXED_LEN: usize = 8 + GameSettings::ENCODED_LEN + 2 + INITIALS + 1 + 2;
+const MAX_PAYLOAD: usize = GAME_FIXED_LEN + MAX_EVENTS * EVENT_LEN;
+const MAX_RECORD_SECTORS: usize = (RECORD_HEADER_LEN + MAX_PAYLOAD).div_ceil(SECTOR_SIZE);
+
+impl GameRecord {
+    /// Writes the payload into `out`, returning its length.
+    fn encode(&self, out: &mut [u8]) -> usize {
+        let events = self.recording.events();
+        out[0..8].copy_from_slice(&self.recording.seed().to_le_bytes());
+        out[8..16].copy_from_slice(&self.recording.settings().encode());
+        out[16..18].copy_from_slice(&self.score.to_le_bytes());
+        out[18..21].copy_from_slice(&self.initials);
+        out[21] = self.recording.is_complete() as u8;
+        out[22..24].copy_from_slice(&(events.len() as u16).to_le_bytes());
+        for (slot, event) in out[GAME_FIXED_LEN..].chunks_exact_mut(EVENT_LEN).zip(events) {
+            slot[..4].copy_from_slice(&event.tick.to_le_bytes());
+            slot[4] = event.command.to_byte();
+        }
+        GAME_FIXED_LEN + events.len() * EVENT_LEN
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, DiskError> {
+        if payload.len() < GAME_FIXED_LEN {
+            return Err(DiskError::Corrupt);
+        }
+        let seed = u64::from_le_bytes(payload[0..8].try_into().unwrap());
+        let settings = GameSettings::decode(payload[8..16].try_into().unwrap()).ok_or(DiskError::Corrupt)?;
+        let score = u16::from_le_bytes([payload[16], payload[17]]);
+        let initials: [u8; INITIALS] = payload[18..21].try_into().unwrap();
+        // The score table only packs capital letters, or 0 for a blank.
+        if !initials.iter().all(|&c| c == 0 || c.is_ascii_uppercase()) {
+            return Err(DiskError::Corrupt);
+        }
+        let count = u16::from_le_bytes([payload[22], payload[23]]) as usize;
+        if payload.len() != GAME_FIXED_LEN + count * EVENT_LEN {
+            return Err(DiskError::Corrupt);
+        }
+        let mut recording = Recording::new(seed, settings);
+        for event in payload[GAME_FIXED_LEN..].chunks_exact(EVENT_LEN) {
+            let tick = u32::from_le_bytes(event[..4].try_into().unwrap());
+            recording.push(tick, Command::from_byte(event[4]).ok_or(DiskError::Corrupt)?);
+        }
+        if payload[21] == 0 {
+            recording.mark_incomplete();
+        }
+        Ok(Self { recording, score, initials })
+    }
+}
+
+/// A record on its way to disk, one sector per `DiskLog::poll`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct Write {
+    sectors: usize,
+    written: usize
+}
+
+/// The game log on one disk.
+pub struct DiskLog {
+    /// First sector past the last committed record.
+    end: u32,
+    games: u32,
+    capacity: u32,
+    /// The record being written, or read back by `for_each_game`.
+    buf: [u8; MAX_RECORD_SECTORS * SECTOR_SIZE],
+    write: Option<Write>
+}
+
+impl DiskLog {
+    fn empty(capacity: u32) -> Self {
+        Self { end: 1, games: 0, capacity, buf: [0; MAX_RECORD_SECTORS * SECTOR_SIZE], write: None }
+    }
+
+    /// Reads the log header off `dev`.
+    pub fn open<D: BlockDevice>(dev: &mut D) -> Result<Self, DiskError> {
+        let mut sector = [0; SECTOR_SIZE];
+        dev.read_sector(0, &mut sector)?;
+        if &sector[..8] != HEADER_MAGIC {
+            return Err(DiskError::Unformatted);
+        }
+        let word = |i: usize| u32::from_le_bytes(sector[i..i + 4].try_into().unwrap());
+        let version = u16::from_le_bytes([sector[8], sector[9]]);
+        if version != VERSION || word(HEADER_LEN - 4) != crc32(&sector[..HEADER_LEN - 4]) {
+            return Err(DiskError::Corrupt);
+        }
+        let mut log = Self::empty(dev.sectors());
+        log.end = word(10);
+        log.games = word(14);
+        if log.end == 0 || log.end > log.capacity {
+            return Err(DiskError::Corrupt);
+        }
+        Ok(log)
+    }
+
+    /// Starts an empty log on `dev`, forgetting anything that was there.
+    pub fn format<D: BlockDevice>(dev: &mut D) -> Result<Self, DiskError> {
+        let log = Self::empty(dev.sectors());
+        if log.capacity < 2 {
+            return Err(DiskError::Full);
+        }
+        log.write_header(dev, log.end, log.games)?;
+        Ok(log)
+    }
+
+    fn write_header<D: BlockDevice>(&self, dev: &mut D, end: u32, games: u32) -> Result<(), DiskError> {
+        let mut sector = [0; SECTOR_SIZE];
+        sector[..8].copy_from_slice(HEADER_MAGIC);
+        sector[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        sector[10..14].copy_from_slice(&end.to_le_bytes());
+        sector[14..18].copy_from_slice(&games.to_le_bytes());
+        let crc = crc32(&sector[..HEADER_LEN - 4]);
+        sector[HEADER_LEN - 4..HEADER_LEN].copy_from_slice(&crc.to_le_bytes());
+        dev.write_sector(0, &sector)
+    }
+
+    pub fn games(&self) -> u32 {
+        self.games
+    }
+
+    /// Whether the last `append` has reached the disk.
+    pub fn is_idle(&self) -> bool {
+        self.write.is_none()
+    }
+
+    fn sector(&self, i: usize) -> &[u8; SECTOR_SIZE] {
+        self.buf[i * SECTOR_SIZE..(i + 1) * SECTOR_SIZE].try_into().unwrap()
+    }
+
+    fn sector_mut(&mut self, i: usize) -> &mut [u8; SECTOR_SIZE] {
+        (&mut self.buf[i * SECTOR_SIZE..(i + 1) * SECTOR_SIZE]).try_into().unwrap()
+    }
+
+    /// Reads back every committed game, oldest first. Only call while idle.
+    pub fn for_each_game<D: BlockDevice>(&mut self, dev: &mut D, mut f: impl FnMut(&GameRecord)) -> Result<(), DiskError> {
+        debug_assert!(self.is_idle());
+        let mut lba = 1;
+        while lba < self.end {
+            dev.read_sector(lba, self.sector_mut(0))?;
+            let len = u32::from_le_bytes(self.buf[4..8].try_into().unwrap()) as usize;
+            if &self.buf[..4] != RECORD_MAGIC || len > MAX_PAYLOAD {
+                return Err(DiskError::Corrupt);
+            }
+            let sectors = (RECORD_HEADER_LEN + len).div_ceil(SECTOR_SIZE);
+            for i in 1..sectors {
+                dev.read_sector(lba + i as u32, self.sector_mut(i))?;
+            }
+            let payload = &self.buf[RECORD_HEADER_LEN..RECORD_HEADER_LEN + len];
+            if u32::from_le_bytes(self.buf[8..12].try_into().unwrap()) != crc32(payload) {
+                return Err(DiskError::Corrupt);
+            }
+            f(&GameRecord::decode(payload)?);
+            lba += sectors as u32;
+        }
+        Ok(())
+    }
+
+    /// Queues `game` to be written by the coming `poll`s. Only call while idle.
+    pub fn append(&mut self, game: &GameRecord) -> Result<(), DiskError> {
+        debug_assert!(self.is_idle());
+        let len = game.encode(&mut self.buf[RECORD_HEADER_LEN..]);
+        let sectors = (RECORD_HEADER_LEN + len).div_ceil(SECTOR_SIZE);
+        if self.end as usize + sectors > self.capacity as usize {
+            return Err(DiskError::Full);
+        }
+        let crc = crc32(&self.buf[RECORD_HEADER_LEN..RECORD_HEADER_LEN + len]);
+        self.buf[..4].copy_from_slice(RECORD_MAGIC);
+        self.buf[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+        self.buf[8..12].copy_from_slice(&crc.to_le_bytes());
+        self.buf[RECORD_HEADER_LEN + len..sectors * SECTOR_SIZE].fill(0);
+        self.write = Some(Write { sectors, written: 0 });
+        Ok(())
+    }
+
+    /// Does one sector's worth of any pending `append`: the record first, then the
+    /// header that commits it. A failed write drops the record.
+    pub fn poll<D: BlockDevice>(&mut self, dev: &mut D) -> Result<(), DiskError> {
+        let Some(write) = self.write.as_mut() else { return Ok(()) };
+        let result = if write.written < write.sectors {
+            let i = write.written;
+            write.written += 1;
+            dev.write_sector(self.end + i as u32, self.sector(i))
+        } else {
+            let (end, games) = (self.end + write.sectors as u32, self.games + 1);
+            self.write = None;
+            self.write_header(dev, end, games).map(|()| {
+                self.end = end;
+                self.games = games;
+            })
+        };
+        if result.is_err() {
+            self.write = None;
+        }
+        result
+    }
+}
+
+/// CRC-32 as used by zip and Ethernet.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 })
+    })
+}
+
+/// Connects an optional save disk to a `SnakeDriver`: `load_into` once at boot, then
+/// `service` on every pass of the main loop. `service` moves at most one sector, so
+/// the game never waits on the disk for long.
+pub struct Storage<D: BlockDevice> {
+    dev: Option<D>,
+    log: Result<DiskLog, DiskError>
+}
+
+impl<D: BlockDevice> Storage<D> {
+    pub fn new(mut dev: Option<D>) -> Self {
+        let log = match dev.as_mut() {
+            Some(dev) => DiskLog::open(dev),
+            None => Err(DiskError::NoDevice)
+        };
+        Self { dev, log }
+    }
+
+    pub fn status(&self) -> DiskStatus {
+        match &self.log {
+            Ok(log) => DiskStatus::Ready { games: log.games() },
+            Err(DiskError::NoDevice) => DiskStatus::Missing,
+            Err(error) => DiskStatus::Failed(*error)
+        }
+    }
+
+    /// Hands every saved game to `kernel`, which keeps their scores and the last replay.
+    pub fn load_into<R: Renderer>(&mut self, kernel: &mut SnakeDriver<R>) {
+        if let (Some(dev), Ok(log)) = (self.dev.as_mut(), self.log.as_mut()) {
+            if let Err(error) = log.for_each_game(dev, |game| kernel.restore_game(game)) {
+                self.log = Err(error);
+            }
+        }
+        kernel.set_disk_status(self.status());
+    }
+
+    /// A finished game is only taken from `kernel` once the last one is on disk, so it
+    /// waits there rather than being dropped while a write is under way.
+    pub fn service<R: Renderer>(&mut self, kernel: &mut SnakeDriver<R>) {
+        if let Some(dev) = self.dev.as_mut() {
+            if kernel.take_format_request() {
+                self.log = DiskLog::format(dev);
+            }
+            if let Ok(log) = self.log.as_mut() {
+                let result = match log.is_idle().then(|| kernel.take_finished_game()).flatten() {
+                    Some(game) => log.append(&game),
+                    None => log.poll(dev)
+                };
+                if let Err(error) = result {
+                    self.log = Err(error);
+                }
+            }
+        }
+        kernel.set_disk_status(self.status());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Grid, KeyCode, DecodedKey, Status, WallMode};
+    use crate::scores::{Entry, HighScores};
+
+    /// A disk in memory that can be told to fail from a given write on.
+    struct MemDisk {
+        sectors: Vec<[u8; SECTOR_SIZE]>,
+        writes_left: usize
+    }
+
+    impl MemDisk {
+        fn new(sectors: usize) -> Self {
+            Self { sectors: vec![[0; SECTOR_SIZE]; sectors], writes_left: usize::MAX }
+        }
+    }
+
+    impl BlockDevice for MemDisk {
+        fn sectors(&self) -> u32 {
+            self.sectors.len() as u32
+        }
+
+        fn read_sector(&mut self, lba: u32, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), DiskError> {
+            *buf = *self.sectors.get(lba as usize).ok_or(DiskError::DeviceError)?;
+            Ok(())
+        }
+
+        fn write_sector(&mut self, lba: u32, buf: &[u8; SECTOR_SIZE]) -> Result<(), DiskError> {
+            if self.writes_left == 0 {
+                return Err(DiskError::Timeout);
+            }
+            self.writes_left -= 1;
+            *self.sectors.get_mut(lba as usize).ok_or(DiskError::DeviceError)? = *buf;
+            Ok(())
+        }
+    }
+
+    fn game(seed: u64, score: u16, events: u32) -> GameRecord {
+        let mut recording = Recording::new(seed, GameSettings::default());
+        for tick in 0..events {
+            recording.push(tick * 3, Command::Turn(crate::Dir::Up));
+        }
+        GameRecord { recording, score, initials: *b"ABC" }
+    }
+
+    fn flush(log: &mut DiskLog, dev: &mut MemDisk) -> Result<(), DiskError> {
+        while !log.is_idle() {
+            log.poll(dev)?;
+        }
+        Ok(())
+    }
+
+    fn read_all(dev: &mut MemDisk) -> Vec<GameRecord> {
+        let mut games = Vec::new();
+        DiskLog::open(dev).unwrap().for_each_game(dev, |g| games.push(*g)).unwrap();
+        games
+    }
+
+    #[test]
+    fn games_survive_reopening() {
+        let mut dev = MemDisk::new(64);
+        assert_eq!(DiskLog::open(&mut dev).err(), Some(DiskError::Unformatted));
+        let mut log = DiskLog::format(&mut dev).unwrap();
+        for (seed, events) in [(1, 0), (2, 300)] {
+            log.append(&game(seed, seed as u16, events)).unwrap();
+            flush(&mut log, &mut dev).unwrap();
+        }
+        assert_eq!(read_all(&mut dev), [game(1, 1, 0), game(2, 2, 300)]);
+        assert_eq!(DiskLog::open(&mut dev).unwrap().games(), 2);
+    }
+
+    #[test]
+    fn a_torn_write_leaves_the_log_as_it_was() {
+        let mut dev = MemDisk::new(64);
+        let mut log = DiskLog::format(&mut dev).unwrap();
+        log.append(&game(1, 5, 10)).unwrap();
+        flush(&mut log, &mut dev).unwrap();
+        dev.writes_left = 2;
+        log.append(&game(2, 6, 300)).unwrap();
+        assert_eq!(flush(&mut log, &mut dev), Err(DiskError::Timeout));
+        assert!(log.is_idle());
+        assert_eq!(read_all(&mut dev), [game(1, 5, 10)]);
+    }
+
+    #[test]
+    fn full_and_damaged_disks_are_reported() {
+        let mut dev = MemDisk::new(3);
+        let mut log = DiskLog::format(&mut dev).unwrap();
+        assert_eq!(log.append(&game(1, 1, 300)), Err(DiskError::Full));
+        log.append(&game(1, 1, 0)).unwrap();
+        flush(&mut log, &mut dev).unwrap();
+        dev.sectors[1][20] ^= 1;
+        let mut log = DiskLog::open(&mut dev).unwrap();
+        assert_eq!(log.for_each_game(&mut dev, |_| {}), Err(DiskError::Corrupt));
+        let mut payload = [0; MAX_PAYLOAD];
+        let len = GameRecord { initials: *b"A?C", ..game(1, 1, 0) }.encode(&mut payload);
+        assert_eq!(GameRecord::decode(&payload[..len]), Err(DiskError::Corrupt));
+        dev.sectors[0][12] ^= 1;
+        assert_eq!(DiskLog::open(&mut dev).err(), Some(DiskError::Corrupt));
+    }
+
+    #[test]
+    fn storage_formats_on_request_and_brings_back_the_last_game() {
+        let mut kernel = SnakeDriver::new(Grid::default());
+        let mut storage = Storage::new(Some(MemDisk::new(128)));
+        storage.load_into(&mut kernel);
+        kernel.open_title();
+        kernel.key(DecodedKey::Unicode('f'));
+        kernel.tick();
+        storage.service(&mut kernel);
+        assert_eq!(storage.status(), DiskStatus::Ready { games: 0 });
+
+        kernel.key(DecodedKey::Unicode('\n'));
+        kernel.tick();
+        kernel.key(DecodedKey::RawKey(KeyCode::ArrowUp));
+        while matches!(kernel.status(), Status::GameOn | Status::Death) {
+            kernel.tick();
+            storage.service(&mut kernel);
+        }
+        for _ in 0..MAX_RECORD_SECTORS + 1 {
+            storage.service(&mut kernel);
+        }
+        assert_eq!(storage.status(), DiskStatus::Ready { games: 1 });
+
+        let mut rebooted = SnakeDriver::new(Grid::default());
+        let Storage { dev, .. } = storage;
+        let mut storage = Storage::new(dev);
+        storage.load_into(&mut rebooted);
+        assert_eq!(rebooted.recording(), kernel.recording());
+        rebooted.open_title();
+        assert!(rebooted.renderer().reads(25, 23, "Save disk: 1 saved"));
+    }
+
+    #[test]
+    fn logged_scores_join_the_ones_from_cmos() {
+        let mut dev = MemDisk::new(64);
+        let mut log = DiskLog::format(&mut dev).unwrap();
+        for score in [9, 4] {
+            log.append(&game(1, score, 0)).unwrap();
+            flush(&mut log, &mut dev).unwrap();
+        }
+        let cmos_only = Entry { initials: *b"XYZ", score: 6 };
+        let mut cmos = HighScores::default();
+        cmos.table_mut(WallMode::Solid).insert(Entry { initials: *b"ABC", score: 9 });
+        cmos.table_mut(WallMode::Solid).insert(cmos_only);
+        let mut kernel = SnakeDriver::new(Grid::default());
+        kernel.restore_record(&crate::persist::Record { settings: GameSettings::default(), scores: cmos });
+        let mut storage = Storage::new(Some(dev));
+        storage.load_into(&mut kernel);
+        let entries = [Entry { initials: *b"ABC", score: 9 }, cmos_only, Entry { initials: *b"ABC", score: 4 }];
+        assert_eq!(kernel.record().scores.table(WallMode::Solid).entries(), entries);
+        assert!(kernel.take_unsaved().is_some());
+
+        let Storage { dev, .. } = storage;
+        Storage::new(dev).load_into(&mut kernel);
+        assert_eq!(kernel.take_unsaved(), None);
+    }
+
+    /// Runs the snake up into the border and through its death.
+    fn crash(kernel: &mut SnakeDriver<Grid>) {
+        kernel.key(DecodedKey::RawKey(KeyCode::ArrowUp));
+        while matches!(kernel.status(), Status::GameOn | Status::Death) {
+            kernel.tick();
+        }
+    }
+
+    #[test]
+    fn a_game_that_ends_mid_write_waits_its_turn() {
+        let mut kernel = SnakeDriver::new(Grid::default());
+        let mut dev = MemDisk::new(128);
+        DiskLog::format(&mut dev).unwrap();
+        let mut storage = Storage::new(Some(dev));
+        crash(&mut kernel);
+        let first = *kernel.recording();
+        storage.service(&mut kernel);
+        kernel.key(DecodedKey::Unicode('r'));
+        kernel.tick();
+        crash(&mut kernel);
+        for _ in 0..2 * (MAX_RECORD_SECTORS + 1) {
+            storage.service(&mut kernel);
+        }
+        assert_eq!(storage.status(), DiskStatus::Ready { games: 2 });
+        let Storage { dev, .. } = storage;
+        let games = read_all(&mut dev.unwrap());
+        assert_eq!([games[0].recording, games[1].recording], [first, *kernel.recording()]);
+        assert_ne!(first.seed(), kernel.recording().seed());
+    }
+}
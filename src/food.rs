@@ -0,0 +1,274 @@
+//! What grows on the board for the snakes to eat.
+//!
+//! There is always one plain apple out. Eating it may bring a bonus food out as well,
+//! drawn from a weighted table, and bonus foods wither if left alone for too long.
+//! Every draw comes from the game's `Rng`, so replays grow the same foods.
+
+use crate::power::Power;
+use crate::rng::Rng;
+use crate::render::{BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::snake::Cell;
+use crate::snapshot::{Reader, Writer};
+
+/// Foods that can sit on the board at once, the apple included.
+pub const MAX_FOODS: usize = 4;
+/// Points for a golden apple.
+pub const GOLDEN_POINTS: usize = 5;
+/// Tail segments a shrink apple takes off.
+pub const SHRINK_SEGMENTS: usize = 3;
+/// Moves made at double speed after eating a speed apple.
+pub const SPEED_MOVES: usize = 40;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FoodKind {
+    /// One point and the usual growth.
+    Apple,
+    /// `GOLDEN_POINTS` points, if it is reached before it withers.
+    Golden,
+    /// Ends the game for a player and knocks a computer snake out.
+    Poison,
+    /// Takes `SHRINK_SEGMENTS` off the tail, and any growth still to come.
+    Shrink,
+    /// One point, and the game runs at double speed for `SPEED_MOVES` moves. Every snake
+    /// moves on the same tick, so the boost hurries the whole board, not just the snake
+    /// that ate it.
+    Speed,
+    /// Nothing to eat, but gives the snake a power-up.
+    Power(Power)
+}
+
+impl FoodKind {
+    /// Points for eating it.
+    pub fn points(self) -> usize {
+        match self {
+            FoodKind::Apple | FoodKind::Speed => 1,
+            FoodKind::Golden => GOLDEN_POINTS,
+            FoodKind::Poison | FoodKind::Shrink | FoodKind::Power(_) => 0
+        }
+    }
+
+    /// Whether eating it makes the snake grow.
+    pub fn feeds(self) -> bool {
+        matches!(self, FoodKind::Apple | FoodKind::Golden | FoodKind::Speed)
+    }
+
+    /// A one-byte form for snapshots.
+    fn to_byte(self) -> u8 {
+        match self {
+            FoodKind::Apple => 0,
+            FoodKind::Golden => 1,
+            FoodKind::Poison => 2,
+            FoodKind::Shrink => 3,
+            FoodKind::Speed => 4,
+            FoodKind::Power(power) => 5 + power.index() as u8
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        Some(match b {
+            0 => FoodKind::Apple,
+            1 => FoodKind::Golden,
+            2 => FoodKind::Poison,
+            3 => FoodKind::Shrink,
+            4 => FoodKind::Speed,
+            b => FoodKind::Power(*Power::ALL.get(b as usize - 5)?)
+        })
+    }
+
+    /// Moves it stays out for, or `None` if it waits to be eaten.
+    fn lifetime(self) -> Option<u32> {
+        match self {
+            FoodKind::Apple => None,
+            FoodKind::Golden => Some(30),
+            FoodKind::Poison | FoodKind::Shrink | FoodKind::Speed => Some(120),
+            FoodKind::Power(_) => Some(60)
+        }
+    }
+}
+
+/// The kind byte of a slot with nothing in it, in a snapshot.
+const EMPTY_SLOT: u8 = 0xFF;
+
+/// What follows an apple, and how often out of the total weight.
+const BONUS_TABLE: [(Option<FoodKind>, u32); 9] = [
+    (None, 8),
+    (Some(FoodKind::Golden), 3),
+    (Some(FoodKind::Poison), 3),
+    (Some(FoodKind::Shrink), 2),
+    (Some(FoodKind::Speed), 2),
+    (Some(FoodKind::Power(Power::Ghost)), 1),
+    (Some(FoodKind::Power(Power::Slow)), 1),
+    (Some(FoodKind::Power(Power::Shield)), 1),
+    (Some(FoodKind::Power(Power::Magnet)), 1)
+];
+
+/// The bonus food, if any, to grow after an apple is eaten.
+pub fn roll_bonus(rng: &mut Rng) -> Option<FoodKind> {
+    let total: u32 = BONUS_TABLE.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.gen_range(0..total as usize) as u32;
+    for (kind, weight) in BONUS_TABLE {
+        if roll < weight {
+            return kind;
+        }
+        roll -= weight;
+    }
+    None
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Food {
+    pub cell: Cell,
+    pub kind: FoodKind,
+    /// Moves until it withers, if it does.
+    left: Option<u32>
+}
+
+impl Food {
+    pub fn new(cell: Cell, kind: FoodKind) -> Self {
+        Self { cell, kind, left: kind.lifetime() }
+    }
+}
+
+/// The foods on the board, in no particular order.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Foods {
+    slots: [Option<Food>; MAX_FOODS]
+}
+
+impl Foods {
+    /// Each slot's cell, kind and moves left (0 if it keeps).
+    pub(crate) const ENCODED_LEN: usize = MAX_FOODS * 5;
+
+    pub fn iter(&self) -> impl Iterator<Item = &Food> + '_ {
+        self.slots.iter().flatten()
+    }
+
+    pub fn at(&self, cell: Cell) -> Option<Food> {
+        self.iter().find(|f| f.cell == cell).copied()
+    }
+
+    /// Where the plain apple is, if one is out.
+    pub fn apple(&self) -> Option<Cell> {
+        self.iter().find(|f| f.kind == FoodKind::Apple).map(|f| f.cell)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(Option::is_some)
+    }
+
+    /// Puts `food` out, unless the board already holds `MAX_FOODS`.
+    pub fn add(&mut self, food: Food) {
+        if let Some(slot) = self.slots.iter_mut().find(|s| s.is_none()) {
+            *slot = Some(food);
+        }
+    }
+
+    /// Removes and returns whatever grows on `cell`.
+    pub fn take(&mut self, cell: Cell) -> Option<Food> {
+        self.slots.iter_mut().find(|s| s.is_some_and(|f| f.cell == cell))?.take()
+    }
+
+    /// Moves whatever grows on `from` over to `to`, keeping the time it has left.
+    pub fn shift(&mut self, from: Cell, to: Cell) -> Option<Food> {
+        let food = self.slots.iter_mut().flatten().find(|f| f.cell == from)?;
+        food.cell = to;
+        Some(*food)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = [None; MAX_FOODS];
+    }
+
+    /// Writes every slot in order, so the foods come back where they were.
+    pub(crate) fn write_to(&self, w: &mut Writer) {
+        for slot in self.slots {
+            match slot {
+                Some(food) => {
+                    w.bytes(&[food.cell.col() as u8, food.cell.row() as u8, food.kind.to_byte()]);
+                    w.u16(food.left.map_or(0, |left| left as u16));
+                }
+                None => w.bytes(&[0, 0, EMPTY_SLOT, 0, 0])
+            }
+        }
+    }
+
+    pub(crate) fn read_from(r: &mut Reader) -> Option<Self> {
+        let mut foods = Self::default();
+        for slot in foods.slots.iter_mut() {
+            let [col, row, kind] = r.bytes()?;
+            let left = r.u16()?;
+            if kind == EMPTY_SLOT {
+                continue;
+            }
+            let kind = FoodKind::from_byte(kind)?;
+            if col as usize >= BUFFER_WIDTH || row as usize >= BUFFER_HEIGHT || (left == 0) != kind.lifetime().is_none() {
+                return None;
+            }
+            let left = kind.lifetime().map(|_| left as u32);
+            *slot = Some(Food { cell: Cell::new(col as usize, row as usize), kind, left });
+        }
+        Some(foods)
+    }
+
+    /// Counts one move off every food that withers, and removes the ones that have run
+    /// out. Returns where they were.
+    pub fn wither(&mut self) -> [Option<Cell>; MAX_FOODS] {
+        let mut gone = [None; MAX_FOODS];
+        for (slot, gone) in self.slots.iter_mut().zip(gone.iter_mut()) {
+            let Some(food) = slot else { continue };
+            if let Some(left) = food.left.as_mut() {
+                *left = left.saturating_sub(1);
+                if *left == 0 {
+                    *gone = Some(food.cell);
+                    *slot = None;
+                }
+            }
+        }
+        gone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bonus_comes_up() {
+        let mut rng = Rng::new(3);
+        let mut seen = [0u32; BONUS_TABLE.len()];
+        for _ in 0..2200 {
+            let kind = roll_bonus(&mut rng);
+            seen[BONUS_TABLE.iter().position(|(k, _)| *k == kind).unwrap()] += 1;
+        }
+        // Each weight point is worth about 100 rolls.
+        for (n, (_, weight)) in seen.iter().zip(BONUS_TABLE) {
+            assert!(n.abs_diff(100 * weight) < 40 * weight, "{seen:?}");
+        }
+    }
+
+    #[test]
+    fn bonus_foods_wither_and_apples_keep() {
+        let mut foods = Foods::default();
+        foods.add(Food::new(Cell::new(1, 1), FoodKind::Apple));
+        foods.add(Food::new(Cell::new(2, 1), FoodKind::Golden));
+        for _ in 1..30 {
+            assert_eq!(foods.wither(), [None; MAX_FOODS]);
+        }
+        assert_eq!(foods.wither(), [None, Some(Cell::new(2, 1)), None, None]);
+        assert_eq!(foods.iter().count(), 1);
+        assert_eq!(foods.apple(), Some(Cell::new(1, 1)));
+        assert_eq!(foods.take(Cell::new(1, 1)).map(|f| f.kind), Some(FoodKind::Apple));
+        assert_eq!(foods.apple(), None);
+    }
+
+    #[test]
+    fn the_board_holds_only_so_much() {
+        let mut foods = Foods::default();
+        for col in 0..MAX_FOODS + 2 {
+            foods.add(Food::new(Cell::new(col, 1), FoodKind::Shrink));
+        }
+        assert!(foods.is_full());
+        assert_eq!(foods.iter().count(), MAX_FOODS);
+        assert_eq!(foods.at(Cell::new(MAX_FOODS, 1)), None);
+    }
+}
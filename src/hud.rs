@@ -0,0 +1,204 @@
+//! The status line along row 0: scores, the game's level, speed and clock, and a
+//! message area on the right that lists running power-ups when there is nothing to say.
+//!
+//! Only fields that changed since the last draw are repainted, and every number is
+//! padded out to its field's width so a shorter one leaves no stale digits behind.
+
+use core::cmp::Ordering;
+
+use crate::food::FoodKind;
+use crate::power::{Effects, Power};
+use crate::render::{Color, ColorCode, Renderer, BUFFER_WIDTH};
+use crate::settings::MAX_PLAYERS;
+use crate::ui::{draw_str, fill, Align, Digits};
+use crate::{Pal, Sym};
+
+/// Where messages go, and how long they can be.
+pub(crate) const NOTE_COL: usize = 56;
+pub(crate) const NOTE_WIDTH: usize = BUFFER_WIDTH - NOTE_COL;
+/// Where each player's label goes in a two-player game; the score follows six columns on.
+const PLAYER_COLS: [usize; MAX_PLAYERS as usize] = [2, 14];
+const SCORE_DIGITS: usize = 5;
+
+/// Everything the status line shows.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct HudFields {
+    pub players: u8,
+    pub scores: [usize; MAX_PLAYERS as usize],
+    /// The best score for the mode so far, this game's included.
+    pub best: usize,
+    pub length: usize,
+    pub level: u8,
+    pub speed: u8,
+    /// Whether the game is running slower or faster than `speed` right now.
+    pub pace: Ordering,
+    pub seconds: u64,
+    /// Player 1's power-ups, shown while there is no note.
+    pub effects: Effects,
+    pub note: &'static str
+}
+
+/// What is on row 0 now, so the next draw can leave alone what hasn't changed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Hud {
+    shown: Option<HudFields>
+}
+
+impl Hud {
+    /// Forgets what is on screen, so the next draw paints the whole row.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    pub fn draw<R: Renderer>(&mut self, hud: HudFields, r: &mut R) {
+        let text = Pal::disp(Pal::Text);
+        let old = self.shown.replace(hud).filter(|old| old.players == hud.players);
+        let fresh = old.is_none();
+        let old = old.unwrap_or(hud);
+        if fresh {
+            for col in 0..BUFFER_WIDTH {
+                r.plot(' ', col, 0, ColorCode::new(Color::Blue, Color::Blue));
+            }
+            if hud.players > 1 {
+                for (p, &col) in PLAYER_COLS.iter().enumerate() {
+                    let end = r.plot_str("P", col, 0, text);
+                    let end = r.plot_num(p as isize + 1, end, 0, text);
+                    r.plot(':', end, 0, text);
+                    let color = if p == 0 { Pal::Snake } else { Pal::Snake2 };
+                    r.plot(Sym::disp(Sym::Head), col + 4, 0, Pal::hud(color));
+                }
+            } else {
+                r.plot_str("SCORE:", 2, 0, text);
+                r.plot_str("HI:", 15, 0, text);
+                r.plot_str("LEN:", 25, 0, text);
+            }
+            r.plot_str("LV:", 35, 0, text);
+            r.plot_str("SPD:", 42, 0, text);
+        }
+
+        if hud.players > 1 {
+            for (p, &col) in PLAYER_COLS.iter().enumerate() {
+                if fresh || hud.scores[p] != old.scores[p] {
+                    plot_padded(r, hud.scores[p], col + 6, SCORE_DIGITS);
+                }
+            }
+        } else {
+            if fresh || hud.scores[0] != old.scores[0] {
+                plot_padded(r, hud.scores[0], 9, SCORE_DIGITS);
+            }
+            if fresh || hud.best != old.best {
+                plot_padded(r, hud.best, 19, SCORE_DIGITS);
+            }
+            if fresh || hud.length != old.length {
+                plot_padded(r, hud.length, 30, 4);
+            }
+        }
+        if fresh || hud.level != old.level {
+            plot_padded(r, hud.level as usize, 39, 2);
+        }
+        if fresh || (hud.speed, hud.pace) != (old.speed, old.pace) {
+            plot_padded(r, hud.speed as usize, 47, 1);
+            let pace = match hud.pace {
+                Ordering::Less => '-',
+                Ordering::Equal => ' ',
+                Ordering::Greater => '+'
+            };
+            r.plot(pace, 48, 0, text);
+        }
+        if fresh || hud.seconds != old.seconds {
+            let seconds = hud.seconds.min(99 * 60 + 59) as usize;
+            plot_two_digits(r, seconds / 60, 50);
+            r.plot(':', 52, 0, text);
+            plot_two_digits(r, seconds % 60, 53);
+        }
+        if fresh || hud.note != old.note || (hud.note.is_empty() && hud.effects != old.effects) {
+            let end = if hud.note.is_empty() {
+                plot_effects(r, hud.effects)
+            } else {
+                r.plot_str(hud.note, NOTE_COL, 0, text)
+            };
+            fill(r, end, NOTE_COL + NOTE_WIDTH, 0, text);
+        }
+    }
+}
+
+/// Writes `n` left-aligned in a field `width` wide, blanking the rest of it. Numbers
+/// too big for the field show as all nines.
+fn plot_padded<R: Renderer>(r: &mut R, n: usize, col: usize, width: usize) {
+    let end = draw_str(r, Digits::capped(n as u64, width).as_str(), col, 0, Align::Left, Pal::disp(Pal::Text));
+    fill(r, end, col + width, 0, Pal::disp(Pal::Text));
+}
+
+fn plot_two_digits<R: Renderer>(r: &mut R, n: usize, col: usize) {
+    r.plot_str(Digits::new(n as u64).pad(2, b'0').as_str(), col, 0, Pal::disp(Pal::Text));
+}
+
+/// Lists each running power-up as its letter and the moves it has left, in the
+/// power-up's colour. Returns the column after the last one.
+fn plot_effects<R: Renderer>(r: &mut R, effects: Effects) -> usize {
+    let mut col = NOTE_COL;
+    for power in Power::ALL.into_iter().filter(|&p| effects.has(p)) {
+        r.plot(power.letter(), col, 0, Pal::hud(Pal::Food(FoodKind::Power(power))));
+        plot_two_digits(r, effects.left(power).min(99) as usize, col + 1);
+        r.plot(' ', col + 3, 0, Pal::disp(Pal::Text));
+        col += 4;
+    }
+    col
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Grid;
+
+    fn fields() -> HudFields {
+        HudFields {
+            players: 1,
+            scores: [0; MAX_PLAYERS as usize],
+            best: 0,
+            length: 1,
+            level: 0,
+            speed: 4,
+            pace: Ordering::Equal,
+            seconds: 0,
+            effects: Effects::default(),
+            note: ""
+        }
+    }
+
+    #[test]
+    fn shrinking_numbers_leave_no_digits_behind() {
+        let (mut hud, mut grid) = (Hud::default(), Grid::default());
+        hud.draw(HudFields { scores: [1234, 0], seconds: 754, ..fields() }, &mut grid);
+        assert!(grid.reads(2, 0, "SCORE: 1234  HI:"));
+        assert!(grid.reads(50, 0, "12:34"));
+        hud.draw(HudFields { scores: [7, 0], pace: Ordering::Greater, ..fields() }, &mut grid);
+        assert!(grid.reads(2, 0, "SCORE: 7     HI:"));
+        assert!(grid.reads(42, 0, "SPD: 4+ 00:00"));
+    }
+
+    #[test]
+    fn only_changed_fields_are_repainted() {
+        let (mut hud, mut grid) = (Hud::default(), Grid::default());
+        hud.draw(fields(), &mut grid);
+        grid.plot('?', 20, 0, Pal::disp(Pal::Text));
+        hud.draw(HudFields { length: 12, ..fields() }, &mut grid);
+        assert_eq!(grid.char_at(20, 0), '?');
+        assert!(grid.reads(25, 0, "LEN: 12"));
+        hud.invalidate();
+        hud.draw(fields(), &mut grid);
+        assert_eq!(grid.char_at(20, 0), ' ');
+    }
+
+    #[test]
+    fn notes_take_the_place_of_power_ups() {
+        let (mut hud, mut grid) = (Hud::default(), Grid::default());
+        let mut effects = Effects::default();
+        effects.grant(Power::Slow);
+        effects.grant(Power::Magnet);
+        hud.draw(HudFields { effects, ..fields() }, &mut grid);
+        assert!(grid.reads(NOTE_COL, 0, "S40 M50 "));
+        hud.draw(HudFields { effects, note: "DEMO", ..fields() }, &mut grid);
+        assert!(grid.reads(NOTE_COL, 0, "DEMO    "));
+    }
+}
@@ -0,0 +1,321 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::settings::MAX_SNAKES;
+use crate::snake::Dir;
+
+/// How many commands `SnakeDriver` holds on to between moves.
+pub const COMMAND_CAPACITY: usize = 4;
+
+/// The byte for the last snake's last `Command::Steer`.
+const STEER_LAST: u8 = 10 + 4 * MAX_SNAKES as u8;
+
+/// Something the player asked the game to do.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Command {
+    /// Steers player 1, or moves around a menu.
+    Turn(Dir),
+    /// Steers snake `n`, counting player 1 as 0: player 2's arrow keys, or a
+    /// computer opponent.
+    Steer(usize, Dir),
+    /// Esc: pause or resume, or leave the game-over screen.
+    Back,
+    Restart,
+    Replay,
+    Options,
+    /// Enter: take the highlighted menu entry.
+    Select,
+    Scores,
+    /// Prepare a blank save disk, when the title screen offers to.
+    Format
+}
+
+impl Command {
+    /// The command for a typed character, steering with `keys`.
+    pub fn from_char(c: char, keys: KeyLayout) -> Option<Command> {
+        if let Some(dir) = keys.turn(c) {
+            return Some(Command::Turn(dir));
+        }
+        match c {
+            '\u{1B}' => Some(Command::Back),
+            'r' => Some(Command::Restart),
+            'v' => Some(Command::Replay),
+            'o' => Some(Command::Options),
+            '\n' => Some(Command::Select),
+            'h' => Some(Command::Scores),
+            'f' => Some(Command::Format),
+            _ => None
+        }
+    }
+
+    /// A one-byte form for saving recordings.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Command::Turn(dir) => dir as u8,
+            Command::Back => 4,
+            Command::Restart => 5,
+            Command::Replay => 6,
+            Command::Options => 7,
+            Command::Select => 8,
+            Command::Scores => 9,
+            Command::Format => 10,
+            Command::Steer(n, dir) => 11 + 4 * n as u8 + dir as u8
+        }
+    }
+
+    /// The player a turn steers and where to, or `None` for anything but a turn.
+    pub fn steering(self) -> Option<(usize, Dir)> {
+        match self {
+            Command::Turn(dir) => Some((0, dir)),
+            Command::Steer(n, dir) => Some((n, dir)),
+            _ => None
+        }
+    }
+
+    pub fn from_byte(b: u8) -> Option<Command> {
+        Some(match b {
+            0 => Command::Turn(Dir::Up),
+            1 => Command::Turn(Dir::Down),
+            2 => Command::Turn(Dir::Left),
+            3 => Command::Turn(Dir::Right),
+            4 => Command::Back,
+            5 => Command::Restart,
+            6 => Command::Replay,
+            7 => Command::Options,
+            8 => Command::Select,
+            9 => Command::Scores,
+            10 => Command::Format,
+            11..=STEER_LAST => Command::Steer((b as usize - 11) / 4, Dir::ALL[(b as usize - 11) % 4]),
+            _ => return None
+        })
+    }
+}
+
+/// Which letter keys steer the snake. The arrow keys work as well, except in
+/// two-player games, where they steer player 2.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KeyLayout {
+    Wasd,
+    /// WASD's positions on an AZERTY keyboard.
+    Zqsd,
+    Ijkl
+}
+
+impl KeyLayout {
+    pub const ALL: [KeyLayout; 3] = [KeyLayout::Wasd, KeyLayout::Zqsd, KeyLayout::Ijkl];
+
+    /// Position in `ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Up, left, down and right, in that order.
+    fn letters(self) -> [char; 4] {
+        match self {
+            KeyLayout::Wasd => ['w', 'a', 's', 'd'],
+            KeyLayout::Zqsd => ['z', 'q', 's', 'd'],
+            KeyLayout::Ijkl => ['i', 'j', 'k', 'l']
+        }
+    }
+
+    pub fn turn(self, c: char) -> Option<Dir> {
+        let dirs = [Dir::Up, Dir::Left, Dir::Down, Dir::Right];
+        self.letters().iter().position(|&l| l == c).map(|i| dirs[i])
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            KeyLayout::Wasd => "WASD",
+            KeyLayout::Zqsd => "ZQSD",
+            KeyLayout::Ijkl => "IJKL"
+        }
+    }
+
+    pub(crate) fn cycle(self, up: bool) -> Self {
+        let n = Self::ALL.len();
+        Self::ALL[(self.index() + if up { 1 } else { n - 1 }) % n]
+    }
+}
+
+/// A fixed-capacity FIFO for use inside one thread of control.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Queue<T: Copy, const N: usize> {
+    items: [Option<T>; N],
+    start: usize,
+    len: usize
+}
+
+impl<T: Copy, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self { items: [None; N], start: 0, len: 0 }
+    }
+}
+
+impl<T: Copy, const N: usize> Queue<T, N> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `item`, or hands it back if the queue is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.len == N {
+            return Err(item);
+        }
+        self.items[(self.start + self.len) % N] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.items[self.start].take();
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        item
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Items from oldest to newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + '_ {
+        (0..self.len).filter_map(move |i| self.items[(self.start + i) % N])
+    }
+}
+
+/// A lock-free single-producer single-consumer ring buffer. The producer side can run
+/// in an interrupt handler while the consumer polls from the main loop, and nothing
+/// pushed is lost unless the queue fills up.
+///
+/// Only one context may call `push` and only one may call `pop`.
+pub struct SpscQueue<T: Copy, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Total pops so far. Written only by the consumer.
+    head: AtomicUsize,
+    /// Total pushes so far. Written only by the producer.
+    tail: AtomicUsize
+}
+
+unsafe impl<T: Copy + Send, const N: usize> Sync for SpscQueue<T, N> {}
+
+impl<T: Copy, const N: usize> Default for SpscQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> SpscQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        }
+    }
+
+    /// Producer side. Returns `false`, dropping `item`, when the queue is full.
+    pub fn push(&self, item: T) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) == N {
+            return false;
+        }
+        // The consumer won't read this slot until it sees the new tail.
+        unsafe { (*self.slots[tail % N].get()).write(item) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Consumer side.
+    pub fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // The producer finished writing this slot before publishing the tail we read,
+        // and won't reuse it until it sees the new head.
+        let item = unsafe { (*self.slots[head % N].get()).assume_init() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_steer_with_their_own_letters() {
+        assert_eq!(Command::from_char('a', KeyLayout::Wasd), Some(Command::Turn(Dir::Left)));
+        assert_eq!(Command::from_char('q', KeyLayout::Zqsd), Some(Command::Turn(Dir::Left)));
+        assert_eq!(Command::from_char('a', KeyLayout::Ijkl), None);
+        assert_eq!(Command::from_char('k', KeyLayout::Ijkl), Some(Command::Turn(Dir::Down)));
+        assert_eq!(KeyLayout::Wasd.cycle(false), KeyLayout::Ijkl);
+    }
+
+    #[test]
+    fn commands_round_trip_through_bytes() {
+        for b in 0..=u8::MAX {
+            if let Some(command) = Command::from_byte(b) {
+                assert_eq!(command.to_byte(), b);
+            }
+        }
+        assert_eq!(Command::from_byte(17).and_then(Command::steering), Some((1, Dir::Left)));
+        assert_eq!(Command::from_byte(STEER_LAST + 1), None);
+    }
+
+    #[test]
+    fn queue_is_fifo_and_bounded() {
+        let mut q: Queue<u8, 3> = Queue::default();
+        assert_eq!(q.push(1), Ok(()));
+        assert_eq!(q.push(2), Ok(()));
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.push(3), Ok(()));
+        assert_eq!(q.push(4), Ok(()));
+        assert_eq!(q.push(5), Err(5));
+        assert_eq!(q.iter().collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!((q.pop(), q.pop(), q.pop(), q.pop()), (Some(2), Some(3), Some(4), None));
+    }
+
+    #[test]
+    fn spsc_keeps_everything_until_full() {
+        let q: SpscQueue<u32, 4> = SpscQueue::new();
+        for i in 0..4 {
+            assert!(q.push(i));
+        }
+        assert!(!q.push(99));
+        assert_eq!(q.pop(), Some(0));
+        assert!(q.push(4));
+        assert_eq!([q.pop(), q.pop(), q.pop(), q.pop(), q.pop()], [Some(1), Some(2), Some(3), Some(4), None]);
+    }
+
+    #[test]
+    fn spsc_across_threads() {
+        static Q: SpscQueue<u32, 8> = SpscQueue::new();
+        let producer = std::thread::spawn(|| {
+            for i in 0..10_000 {
+                while !Q.push(i) {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 10_000 {
+            match Q.pop() {
+                Some(i) => {
+                    assert_eq!(i, expected);
+                    expected += 1;
+                }
+                None => std::thread::yield_now()
+            }
+        }
+        producer.join().unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! Level maps.
+//!
+//! A level is a plain-text picture of the playfield, one line per row:
+//!
+//! * `.` or space: open floor
+//! * `#`: wall, which kills like the border does
+//! * `>` `<` `^` `v`: where the snake starts, and the way it faces (exactly one)
+//! * `@`: where the snake starts, facing right
+//! * `*`: open floor where apples may grow. If a level has any of these, apples
+//!   only grow on them while one is free.
+//!
+//! Lines starting with `;` are comments, and the first one names the level. Every row
+//! must be the same width. The map is the playfield only: the border and HUD are
+//! added around it.
+
+use core::fmt;
+
+use crate::settings::{GameSettings, MAX_HEIGHT, MAX_WIDTH, MIN_HEIGHT, MIN_WIDTH};
+use crate::snake::{Cell, Dir};
+
+pub const MAX_LEVEL_WIDTH: usize = MAX_WIDTH as usize - 2;
+pub const MAX_LEVEL_HEIGHT: usize = MAX_HEIGHT as usize - 2;
+pub const MIN_LEVEL_WIDTH: usize = MIN_WIDTH as usize - 2;
+pub const MIN_LEVEL_HEIGHT: usize = MIN_HEIGHT as usize - 2;
+
+/// The built-in maps. Index 0 is always the open arena sized by the settings, so these
+/// are levels 1 and up.
+const SOURCES: [&str; 3] = [
+    include_str!("../levels/pillars.txt"),
+    include_str!("../levels/crossroads.txt"),
+    include_str!("../levels/corridors.txt")
+];
+
+/// Number of selectable levels, counting the open arena.
+pub const COUNT: usize = SOURCES.len() + 1;
+
+/// The name of level `index` as shown on the options screen, without parsing the map.
+pub fn name(index: usize) -> &'static str {
+    match index {
+        0 => Level::open().name,
+        _ => SOURCES.get(index - 1).map_or("", |source| title(source))
+    }
+}
+
+/// The first comment line, or a placeholder for maps without one.
+fn title(source: &'static str) -> &'static str {
+    source.lines().find_map(|line| line.strip_prefix(';')).map_or("Untitled", str::trim)
+}
+
+/// Every selectable level, parsed. A map that fails to parse stays in the list as its
+/// error so it can be reported instead of silently skipped.
+pub fn load_all() -> [Result<Level, LevelError>; COUNT] {
+    core::array::from_fn(|i| match i {
+        0 => Ok(Level::open()),
+        _ => Level::parse(SOURCES[i - 1])
+    })
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LevelError {
+    /// No map rows at all.
+    Empty,
+    /// A character outside the legend.
+    UnknownChar { line: usize, ch: char },
+    /// A row whose width differs from the first row's.
+    RaggedRow { line: usize, expected: usize, found: usize },
+    TooWide { width: usize },
+    TooNarrow { width: usize },
+    TooTall { height: usize },
+    TooShort { height: usize },
+    NoSpawn,
+    SecondSpawn { line: usize }
+}
+
+impl LevelError {
+    /// A short description that fits in a HUD note.
+    pub fn message(&self) -> &'static str {
+        match self {
+            LevelError::Empty => "map has no rows",
+            LevelError::UnknownChar { .. } => "unknown map character",
+            LevelError::RaggedRow { .. } => "rows differ in width",
+            LevelError::TooWide { .. } => "map too wide",
+            LevelError::TooNarrow { .. } => "map too narrow",
+            LevelError::TooTall { .. } => "map too tall",
+            LevelError::TooShort { .. } => "map too short",
+            LevelError::NoSpawn => "no spawn point",
+            LevelError::SecondSpawn { .. } => "more than one spawn"
+        }
+    }
+
+    /// The 1-based source line the problem is on, where there is one.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            LevelError::UnknownChar { line, .. }
+            | LevelError::RaggedRow { line, .. }
+            | LevelError::SecondSpawn { line } => Some(*line),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line() {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{}", self.message())?;
+        match self {
+            LevelError::UnknownChar { ch, .. } => write!(f, " {:?}", ch),
+            LevelError::RaggedRow { expected, found, .. } => write!(f, " (expected {}, found {})", expected, found),
+            LevelError::TooWide { width } | LevelError::TooNarrow { width } =>
+                write!(f, " ({} columns, must be {} to {})", width, MIN_LEVEL_WIDTH, MAX_LEVEL_WIDTH),
+            LevelError::TooTall { height } | LevelError::TooShort { height } =>
+                write!(f, " ({} rows, must be {} to {})", height, MIN_LEVEL_HEIGHT, MAX_LEVEL_HEIGHT),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LevelError {}
+
+/// A parsed map. Cells are counted from the playfield's top-left corner, so a level
+/// can be placed on any board that is big enough.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Level {
+    name: &'static str,
+    /// Zero for the open arena, which takes its size from the settings.
+    width: u8,
+    height: u8,
+    walls: [u128; MAX_LEVEL_HEIGHT],
+    zones: [u128; MAX_LEVEL_HEIGHT],
+    has_zones: bool,
+    spawn: (u8, u8),
+    facing: Dir
+}
+
+impl Level {
+    /// No walls; the board size and start position come from the settings.
+    pub fn open() -> Self {
+        Self {
+            name: "Open field",
+            width: 0,
+            height: 0,
+            walls: [0; MAX_LEVEL_HEIGHT],
+            zones: [0; MAX_LEVEL_HEIGHT],
+            has_zones: false,
+            spawn: (0, 0),
+            facing: Dir::Right
+        }
+    }
+
+    pub fn parse(source: &'static str) -> Result<Self, LevelError> {
+        let mut level = Self { name: title(source), ..Self::open() };
+        let mut spawn = None;
+        let mut rows = 0;
+        for (i, line) in source.lines().enumerate() {
+            let line_no = i + 1;
+            if line.starts_with(';') {
+                continue;
+            }
+            let width = line.chars().count();
+            if rows == 0 {
+                if width > MAX_LEVEL_WIDTH {
+                    return Err(LevelError::TooWide { width });
+                }
+                if width < MIN_LEVEL_WIDTH {
+                    return Err(LevelError::TooNarrow { width });
+                }
+                level.width = width as u8;
+            } else if width != level.width as usize {
+                return Err(LevelError::RaggedRow { line: line_no, expected: level.width as usize, found: width });
+            }
+            if rows == MAX_LEVEL_HEIGHT {
+                return Err(LevelError::TooTall { height: rows + 1 });
+            }
+            for (col, ch) in line.chars().enumerate() {
+                let facing = match ch {
+                    '.' | ' ' => None,
+                    '#' => {
+                        level.walls[rows] |= 1 << col;
+                        None
+                    }
+                    '*' => {
+                        level.zones[rows] |= 1 << col;
+                        level.has_zones = true;
+                        None
+                    }
+                    '@' | '>' => Some(Dir::Right),
+                    '<' => Some(Dir::Left),
+                    '^' => Some(Dir::Up),
+                    'v' => Some(Dir::Down),
+                    _ => return Err(LevelError::UnknownChar { line: line_no, ch })
+                };
+                if let Some(facing) = facing {
+                    if spawn.is_some() {
+                        return Err(LevelError::SecondSpawn { line: line_no });
+                    }
+                    spawn = Some(((col as u8, rows as u8), facing));
+                }
+            }
+            rows += 1;
+        }
+        if rows == 0 {
+            return Err(LevelError::Empty);
+        }
+        if rows < MIN_LEVEL_HEIGHT {
+            return Err(LevelError::TooShort { height: rows });
+        }
+        level.height = rows as u8;
+        let (at, facing) = spawn.ok_or(LevelError::NoSpawn)?;
+        level.spawn = at;
+        level.facing = facing;
+        Ok(level)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.width == 0
+    }
+
+    /// `settings` with the board resized to fit this level, unless it is the open arena.
+    pub fn fit(&self, settings: GameSettings) -> GameSettings {
+        if self.is_open() {
+            return settings;
+        }
+        let border = if settings.walls.has_border() { 2 } else { 0 };
+        GameSettings { width: self.width + border, height: self.height + border, ..settings }
+    }
+
+    fn local(&self, rules: &GameSettings, cell: Cell) -> Option<(usize, usize)> {
+        let origin = rules.playfield_cell(0);
+        let col = cell.col().checked_sub(origin.col())?;
+        let row = cell.row().checked_sub(origin.row())?;
+        if col < self.width as usize && row < self.height as usize { Some((col, row)) } else { None }
+    }
+
+    pub fn is_wall(&self, rules: &GameSettings, cell: Cell) -> bool {
+        self.local(rules, cell).is_some_and(|(col, row)| self.walls[row] & 1 << col != 0)
+    }
+
+    /// Whether apples are meant to grow on `cell`. Without zones, anywhere will do.
+    pub fn in_zone(&self, rules: &GameSettings, cell: Cell) -> bool {
+        !self.has_zones || self.local(rules, cell).is_some_and(|(col, row)| self.zones[row] & 1 << col != 0)
+    }
+
+    /// Where snake `player` starts and which way it faces. The second snake starts
+    /// from the mirror image of the first's spawn across the middle of the playfield,
+    /// and any more start half the playfield below the two before them, moved on past
+    /// any wall.
+    pub fn spawn(&self, rules: &GameSettings, player: usize) -> (Cell, Dir) {
+        if player >= 2 {
+            let (above, facing) = self.spawn(rules, player - 2);
+            let (_, h) = rules.playfield_size();
+            let top = rules.playfield_cell(0).row();
+            let start = (0..h)
+                .map(|i| Cell::new(above.col(), top + (above.row() - top + h / 2 + i) % h))
+                .find(|&cell| !self.is_wall(rules, cell))
+                .unwrap_or(above);
+            return (start, facing);
+        }
+        let (start, facing) = if self.is_open() {
+            (rules.start(), Dir::Right)
+        } else {
+            let origin = rules.playfield_cell(0);
+            (Cell::new(origin.col() + self.spawn.0 as usize, origin.row() + self.spawn.1 as usize), self.facing)
+        };
+        if player == 0 {
+            return (start, facing);
+        }
+        let (w, _) = rules.playfield_size();
+        let left = rules.playfield_cell(0).col();
+        let facing = if matches!(facing, Dir::Left | Dir::Right) { facing.opposite() } else { facing };
+        (Cell::new(2 * left + w - 1 - start.col(), start.row()), facing)
+    }
+
+    /// Wall cells, in reading order.
+    pub fn walls<'a>(&'a self, rules: &'a GameSettings) -> impl Iterator<Item = Cell> + 'a {
+        let origin = rules.playfield_cell(0);
+        (0..self.height as usize).flat_map(move |row| {
+            (0..self.width as usize)
+                .filter(move |col| self.walls[row] & 1 << col != 0)
+                .map(move |col| Cell::new(origin.col() + col, origin.row() + row))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::MAX_SNAKES;
+
+    const SMALL: &str = "; Tiny\n\
+                         ..................\n\
+                         ..#...............\n\
+                         ..#...v...........\n\
+                         ..#...........**..\n\
+                         ..................\n\
+                         ..................\n\
+                         ..................\n\
+                         ..................\n";
+
+    #[test]
+    fn built_in_levels_parse() {
+        for (i, level) in load_all().iter().enumerate() {
+            assert!(level.is_ok(), "level {}: {}", i, level.unwrap_err());
+            let level = level.unwrap();
+            let rules = level.fit(GameSettings::default());
+            for snake in 1..MAX_SNAKES {
+                let (start, _) = level.spawn(&rules, snake);
+                assert!(rules.in_playfield(start) && !level.is_wall(&rules, start), "level {} snake {}", i, snake);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_walls_spawn_and_zones() {
+        let level = Level::parse(SMALL).unwrap();
+        let rules = level.fit(GameSettings::default());
+        assert_eq!(level.name(), "Tiny");
+        assert_eq!((rules.width, rules.height), (20, 10));
+        assert_eq!(level.spawn(&rules, 0), (Cell::new(7, 4), Dir::Down));
+        assert_eq!(level.spawn(&rules, 1), (Cell::new(12, 4), Dir::Down));
+        assert_eq!(level.spawn(&rules, 3), (Cell::new(12, 8), Dir::Down));
+        assert!(level.is_wall(&rules, Cell::new(3, 3)));
+        assert!(!level.is_wall(&rules, Cell::new(4, 3)));
+        assert!(level.in_zone(&rules, Cell::new(15, 5)));
+        assert!(!level.in_zone(&rules, Cell::new(4, 3)));
+        assert_eq!(level.walls(&rules).count(), 3);
+    }
+
+    #[test]
+    fn reports_bad_maps() {
+        assert_eq!(Level::parse("; nothing\n"), Err(LevelError::Empty));
+        assert_eq!(Level::parse(".....\n"), Err(LevelError::TooNarrow { width: 5 }));
+        let ragged = "..................\n.........>........\n.................\n";
+        assert_eq!(Level::parse(ragged), Err(LevelError::RaggedRow { line: 3, expected: 18, found: 17 }));
+        let edited = |from: &str, to: &str| Level::parse(SMALL.replace(from, to).leak());
+        assert_eq!(edited("v", "."), Err(LevelError::NoSpawn));
+        assert_eq!(edited("**", "<>"), Err(LevelError::SecondSpawn { line: 5 }));
+        assert_eq!(edited("#", "x"), Err(LevelError::UnknownChar { line: 3, ch: 'x' }));
+        assert_eq!(edited("..................\n..................\n", ""), Err(LevelError::TooShort { height: 4 }));
+        assert_eq!(format!("{}", LevelError::RaggedRow { line: 3, expected: 18, found: 17 }),
+            "line 3: rows differ in width (expected 18, found 17)");
+    }
+}
@@ -1,15 +1,65 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(feature = "std", feature = "vga"))]
+compile_error!("the `vga` backend only works in the kernel; build with `--no-default-features --features std`");
+
+pub mod ai;
+pub mod ata;
+mod cmos;
+mod death;
+pub mod disk;
+pub mod food;
+mod hud;
+pub mod input;
+pub mod level;
+mod pause;
+pub mod persist;
+pub mod power;
+mod render;
+pub mod replay;
+mod rewind;
+pub mod rng;
+pub mod scores;
+pub mod settings;
+mod snake;
+pub mod snapshot;
+pub mod stats;
+pub mod title;
+pub mod ui;
 
 use num::Integer;
 use pc_keyboard::{DecodedKey, KeyCode};
-use pluggable_interrupt_os::vga_buffer::{
-    plot, plot_num, Color, ColorCode, BUFFER_HEIGHT, BUFFER_WIDTH, peek, clear_screen
-};
 
-use Status::{GameOn, Paused, Death, GameOver, StartScreen};
-use Sym::{Body, Head, Apple, Doug, Start, Empty, NaN};
-use Pal::{Snake, Appl, Wall, Text, EmptySpace};
+pub use render::{Color, ColorCode, Grid, Renderer, BUFFER_HEIGHT, BUFFER_WIDTH};
+#[cfg(feature = "vga")]
+pub use render::Vga;
+pub use disk::{DiskStatus, GameRecord, Storage};
+pub use food::{Food, FoodKind, Foods};
+pub use input::{Command, KeyLayout, SpscQueue};
+pub use level::{Level, LevelError};
+pub use power::{Effects, Power};
+pub use replay::{Recording, ReplaySpeed};
+pub use rng::Rng;
+pub use ai::Skill;
+pub use settings::{GameSettings, WallMode, MAX_PLAYERS, MAX_SNAKES};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use snake::{Cell, Dir, Player, SnakeBody, MAX_LENGTH};
+pub use stats::{Cause, RunStats};
+
+use Status::{GameOn, Paused, Resuming, Rewinding, Death, GameOver, StartScreen, Options, HighScores, EnterName, Halted};
+use Sym::{Body, Head, Doug, Start, Empty};
+use Pal::{Snake, Snake2, Rival, Wall, Warp, Text, Highlight, EmptySpace};
 use BodyTrail::{Hori, Vert, Right2Up, Left2Up, Right2Down, Left2Down};
+use death::DeathScene;
+use hud::{Hud, HudFields};
+use input::{Queue, COMMAND_CAPACITY};
+use pause::{PauseItem, PauseMenu};
+use replay::Playback;
+use rewind::Rewind;
+use scores::{Entry, NameEntry, INITIALS};
+use settings::OptionsMenu;
+use title::{MenuItem, TitleMenu};
+use ui::{draw_box, draw_str, Align, Frame, Rect};
 
 use core::{
     clone::Clone,
@@ -18,28 +68,99 @@ use core::{
     prelude::rust_2024::derive
 };
 
-const APPLE_STALL_TICKS: usize = 3;
-const UPDATE_FREQUENCY: usize = 1;
+const DEFAULT_SEED: u64 = BUFFER_WIDTH as u64;
+/// Timer ticks without a key press on the title screen before the demo starts: about
+/// half a minute at the timer's 18.2Hz.
+const ATTRACT_TICKS: u32 = 18 * 30;
+/// Quick-save slots. They are kept in memory only, so they don't outlive a reboot.
+const SAVE_SLOTS: usize = 3;
+/// Timer ticks a passing message stays on the HUD: about two seconds.
+const FLASH_TICKS: u32 = 36;
+/// Timer ticks without a Backspace before a rewind counts as let go. The keyboard waits
+/// about half a second before repeating a held key, so this has to outlast that.
+const RELEASE_TICKS: u32 = 12;
 
 #[derive(Copy, Clone, Eq, PartialEq)]
-pub struct SnakeDriver {
-    col: usize,
-    row: usize,
-    oldcol: usize,
-    oldrow: usize,
-    dx: usize,
-    dy: usize,
-    score: usize,
-    length: usize,
+pub struct SnakeDriver<R: Renderer> {
+    renderer: R,
+    /// What the player has chosen; picked up by the next game.
+    settings: GameSettings,
+    /// What the current game is being played with.
+    rules: GameSettings,
+    /// Every level map, parsed once up front.
+    levels: [Result<Level, LevelError>; level::COUNT],
+    /// The map the current game is played on.
+    level: Level,
+    options: OptionsMenu,
+    options_return: Status,
+    /// The menu over the board while paused, and the count back in afterwards.
+    pause: PauseMenu,
+    title: TitleMenu,
+    /// Whether any game has ended yet, so there is one to replay.
+    played: bool,
+    /// Ticks the title screen has gone without a key press.
+    idle: u32,
+    /// Set while the autopilot plays a demo game behind the title.
+    demo: bool,
+    /// Set once a save has been loaded into the current game, which keeps it off the
+    /// high score table.
+    loaded: bool,
+    high_scores: scores::HighScores,
+    scores_return: Status,
+    /// The mode whose table the high score screen shows.
+    scores_shown: WallMode,
+    scores_highlight: Option<usize>,
+    name: NameEntry,
+    /// Set when settings or scores change, until the kernel saves them.
+    unsaved: bool,
+    disk: DiskStatus,
+    format_requested: bool,
+    /// Set when a live game has ended, until the kernel logs it to disk.
+    unlogged: bool,
+    /// What the player entered for the last game, or zeros.
+    last_initials: [u8; INITIALS],
+    /// The players, then their computer opponents. Only the first `rules.snakes()`
+    /// are in the game.
+    players: [Player; MAX_SNAKES],
+    /// How player 1's current game has gone.
+    stats: RunStats,
+    /// Where the last live game landed on the high score table, if it did.
+    best_rank: Option<usize>,
+    /// The player who won the last two-player round, or `None` for a draw.
+    winner: Option<usize>,
+    /// Rounds won by each player since the title screen.
+    wins: [u16; MAX_PLAYERS as usize],
     status: Status,
-    apple_x: usize,
-    apple_y: usize,
-    apple_effect: usize,
+    /// The crash being played out while `Death`.
+    death: DeathScene,
+    hud: Hud,
+    /// The message on the right of the HUD, if there is one.
+    note: &'static str,
+    /// Ticks of play left before a passing message comes down, or 0 if it stays.
+    note_left: u32,
+    /// Quick-saves of games in progress.
+    saves: [Option<Snapshot>; SAVE_SLOTS],
+    /// The slot F5 and F9 use.
+    save_slot: usize,
+    /// A practice game's last moves, for Backspace to wind back through.
+    rewind: Rewind,
+    /// Backspace presses not yet carried out while `Rewinding`.
+    rewind_steps: u8,
+    /// Ticks left before the rewind counts as let go.
+    rewind_held: u32,
+    food: Foods,
+    /// Moves left at double speed from a speed apple.
+    boost: usize,
     countdown: usize,
-    seed: u32,
-    tail_col: usize,
-    tail_row: usize,
-    input_buffer: (u8, u8)
+    seed: u64,
+    seeds: Rng,
+    rng: Rng,
+    ticks: u32,
+    recording: Recording,
+    playback: Option<Playback>,
+    /// Player 2's turns wait in their own queue, so neither player's presses hold up
+    /// the other's.
+    commands: [Queue<Command, COMMAND_CAPACITY>; MAX_PLAYERS as usize]
 }
 
 pub fn safe_add<const LIMIT: usize>(a: usize, b: usize) -> usize {
@@ -54,459 +175,1263 @@ pub fn sub1<const LIMIT: usize>(value: usize) -> usize {
     safe_add::<LIMIT>(value, LIMIT - 1)
 }
 
-pub fn safe_peek(col: usize, row: usize) -> (Sym, ColorCode) {
-    if col >= BUFFER_WIDTH || row >= BUFFER_HEIGHT { (NaN, ColorCode::new(Color::Black, Color::Black)) }
-    else { 
-        let a: (char, ColorCode) = peek(col, row);
-        (Sym::from(a.0), a.1) 
+impl<R: Renderer + Default> Default for SnakeDriver<R> {
+    fn default() -> Self {
+        Self::new(R::default())
     }
 }
 
-impl Default for SnakeDriver {
-    fn default() -> Self {
-        Self {
-            col: BUFFER_WIDTH / 4, 
-            row: BUFFER_HEIGHT / 2, 
-            oldcol: BUFFER_WIDTH / 4, 
-            oldrow: BUFFER_HEIGHT / 2, 
-            dx: 1, 
-            dy: 0, 
-            score: 0, 
-            length: 0, 
-            status: GameOn, 
-            apple_x: 0, 
-            apple_y: 0, 
-            apple_effect: APPLE_STALL_TICKS * UPDATE_FREQUENCY, 
-            countdown: UPDATE_FREQUENCY, 
-            seed: BUFFER_WIDTH as u32, 
-            tail_col: BUFFER_WIDTH / 4, 
-            tail_row: BUFFER_HEIGHT / 2,
-            input_buffer: (0, 0)
+impl<R: Renderer> SnakeDriver<R> {
+
+    pub fn new(renderer: R) -> Self {
+        Self::with_seed(renderer, DEFAULT_SEED)
+    }
+
+    /// A driver whose games are seeded from a sequence started by `seed`, so the whole
+    /// session is reproducible.
+    pub fn with_seed(renderer: R, seed: u64) -> Self {
+        Self::with_settings(renderer, seed, GameSettings::default())
+    }
+
+    pub fn with_settings(renderer: R, seed: u64, settings: GameSettings) -> Self {
+        let mut seeds = Rng::new(seed);
+        let seed = seeds.next_u64();
+        let mut driver = Self {
+            renderer,
+            settings,
+            rules: settings,
+            levels: level::load_all(),
+            level: Level::open(),
+            options: OptionsMenu::default(),
+            options_return: GameOn,
+            pause: PauseMenu::default(),
+            title: TitleMenu::default(),
+            played: false,
+            idle: 0,
+            demo: false,
+            loaded: false,
+            high_scores: scores::HighScores::default(),
+            scores_return: StartScreen,
+            scores_shown: settings.walls,
+            scores_highlight: None,
+            name: NameEntry::default(),
+            unsaved: false,
+            disk: DiskStatus::Missing,
+            format_requested: false,
+            unlogged: false,
+            last_initials: [0; INITIALS],
+            players: [Player::new(settings.start(), Dir::Right, 0); MAX_SNAKES],
+            stats: RunStats::default(),
+            best_rank: None,
+            winner: None,
+            wins: [0; MAX_PLAYERS as usize],
+            status: GameOn,
+            death: DeathScene::default(),
+            hud: Hud::default(),
+            note: "",
+            note_left: 0,
+            saves: [None; SAVE_SLOTS],
+            save_slot: 0,
+            rewind: Rewind::default(),
+            rewind_steps: 0,
+            rewind_held: 0,
+            food: Foods::default(),
+            boost: 0,
+            countdown: 0,
+            seed,
+            seeds,
+            rng: Rng::new(seed),
+            ticks: 0,
+            recording: Recording::new(seed, settings),
+            playback: None,
+            commands: [Queue::default(); MAX_PLAYERS as usize]
+        };
+        driver.apply_rules(settings);
+        driver.init_game(seed);
+        driver
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Player 1's score.
+    pub fn score(&self) -> usize {
+        self.players[0].score
+    }
+
+    /// Player 1's snake.
+    pub fn body(&self) -> &SnakeBody {
+        &self.players[0].body
+    }
+
+    /// The snakes in the current game, players first, including opponents that have
+    /// crashed out.
+    pub fn players(&self) -> &[Player] {
+        &self.players[..self.rules.snakes()]
+    }
+
+    /// Who won the last two-player round, or `None` for a draw.
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+
+    /// The seed the current game started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The settings the next game will use.
+    pub fn settings(&self) -> &GameSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GameSettings) {
+        self.settings = settings;
+    }
+
+    /// Takes back what was saved before the last reboot.
+    pub fn restore_record(&mut self, record: &persist::Record) {
+        self.settings = record.settings;
+        self.high_scores = record.scores;
+    }
+
+    /// What should outlive a reboot right now.
+    pub fn record(&self) -> persist::Record {
+        persist::Record { settings: self.settings, scores: self.high_scores }
+    }
+
+    /// Keeps the score and recording of a game read back from disk. Scores already in
+    /// the table, most likely restored from CMOS, aren't added twice; any that are new
+    /// are saved back to CMOS.
+    pub fn restore_game(&mut self, game: &GameRecord) {
+        if game.initials != [0; INITIALS] {
+            let entry = Entry { initials: game.initials, score: game.score };
+            let table = self.high_scores.table_mut(game.recording.settings().walls);
+            if !table.contains(entry) && table.insert(entry).is_some() {
+                self.unsaved = true;
+            }
         }
+        self.recording = game.recording;
+        self.played = true;
     }
-}
 
-impl SnakeDriver {
+    /// The last live game, if it has ended since the last call.
+    pub fn take_finished_game(&mut self) -> Option<GameRecord> {
+        if !self.unlogged {
+            return None;
+        }
+        self.unlogged = false;
+        Some(GameRecord { recording: self.recording, score: self.final_score(), initials: self.last_initials })
+    }
+
+    /// Whether the player asked for the save disk to be formatted since the last call.
+    pub fn take_format_request(&mut self) -> bool {
+        core::mem::take(&mut self.format_requested)
+    }
+
+    pub fn set_disk_status(&mut self, disk: DiskStatus) {
+        if disk != self.disk {
+            self.disk = disk;
+            if self.status == StartScreen {
+                self.draw_title();
+            }
+        }
+    }
+
+    /// The record to save, if anything in it changed since the last call.
+    pub fn take_unsaved(&mut self) -> Option<persist::Record> {
+        if !self.unsaved {
+            return None;
+        }
+        self.unsaved = false;
+        Some(self.record())
+    }
+
+    /// The inputs of the current game so far, or of the last one once it is over.
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.playback.is_some()
+    }
 
     pub fn tick(&mut self) {
-        self.seed += 1;
-        
+        let ticks = match self.playback.as_mut() {
+            // Nothing is steered while a crash or the count back in plays out, so a
+            // stepped replay doesn't stop there.
+            Some(_) if matches!(self.status, Death | Resuming) => 1,
+            Some(playback) => match playback.speed {
+                ReplaySpeed::Normal => 1,
+                ReplaySpeed::Fast => replay::FAST_TICKS,
+                ReplaySpeed::Step if playback.steps_requested > 0 => {
+                    playback.steps_requested -= 1;
+                    self.countdown + 1
+                }
+                ReplaySpeed::Step => 0
+            },
+            None => 1
+        };
+        for _ in 0..ticks {
+            self.advance();
+        }
+        if self.status == GameOver && self.playback.is_some() {
+            self.playback = None;
+        }
+    }
+
+    /// Runs one timer tick of the game itself, whether live or replayed.
+    fn advance(&mut self) {
         match self.status {
             GameOn => {
-                plot('G', BUFFER_WIDTH / 2, 0, ColorCode::new(Color::White, Color::Blue));
-                plot_num(self.score as isize, 9, 0, Pal::disp(Text));
+                if self.note_left > 0 {
+                    self.note_left -= 1;
+                    if self.note_left == 0 { self.plot_note("") }
+                }
+                self.update_hud();
 
+                self.ticks += 1;
+                self.stats.ticks += 1;
                 if self.countdown == 0 {
-                    if self.apple_effect != 0 {
-                        self.apple_effect -= 1;
-                        self.length += 1;
-                    } else {
-                        self.erase_tail();
-                        //plot_num(self.tail_col as isize, self.col, 0, Pal::disp(Text));
+                    if self.rules.practice {
+                        if let Some(snapshot) = self.snapshot() { self.rewind.push(&snapshot) }
+                    }
+                    self.consume_input();
+                    if self.demo { self.steer_demo() }
+                    self.steer_opponents();
+                    self.update_location();
+                    if self.demo && self.status != GameOn {
+                        self.open_title();
+                        return;
                     }
-                    
-                    self.handle_input(self.input_buffer.0 as char);
-                    self.input_buffer = (self.input_buffer.1, 0);
-                    self.replace_current();
-                    self.draw_current();
-                    match safe_peek(self.apple_x, self.apple_y) { (Apple, _) => {} _ => self.place_apple() }
-
-                    self.countdown = UPDATE_FREQUENCY;
+                    self.wither_food();
+                    if self.food.apple().is_none() { self.place_food(FoodKind::Apple) }
+
+                    self.countdown = self.move_delay();
                 } else { self.countdown -= 1; }
             }
             Paused => {
-                self.handle_input(self.input_buffer.0 as char);
-                self.input_buffer = (self.input_buffer.1, 0);
-                plot('P', BUFFER_WIDTH / 2, 0, ColorCode::new(Color::White, Color::Blue));}
+                self.ticks += 1;
+                self.consume_input();
+            }
+            Resuming => {
+                self.ticks += 1;
+                if !self.pause.count_down(&mut self.renderer) {
+                    self.status = GameOn;
+                }
+            }
+            Rewinding => {
+                if self.rewind_steps > 0 {
+                    self.rewind_steps -= 1;
+                    self.step_back();
+                }
+                self.rewind_held = self.rewind_held.saturating_sub(1);
+                if self.rewind_held == 0 {
+                    self.status = Resuming;
+                    self.pause.start_count(&mut self.renderer);
+                    self.plot_note("");
+                }
+            }
             Death => {
-                plot('D', BUFFER_WIDTH / 2, 0, ColorCode::new(Color::White, Color::Blue));
-                self.status = GameOver;
+                let count = self.rules.snakes();
+                if !self.death.advance(&self.players[..count], &mut self.renderer) {
+                    self.end_game();
+                }
             }
             GameOver => {
-                self.handle_input(self.input_buffer.0 as char);
-                self.input_buffer = (self.input_buffer.1, 0);
-                plot('X', BUFFER_WIDTH / 2, 0, ColorCode::new(Color::White, Color::Blue));}
+                self.consume_input();
+                if self.status == GameOver {
+                    // The results panel lists the rest of the options.
+                    self.plot_note(match (self.rules.players > 1, self.recording.is_complete()) {
+                        (false, _) if self.loaded && !self.rules.practice => "Loaded save: unranked",
+                        (false, _) => "H: scores",
+                        (true, true) => "R: retry  V: replay",
+                        (true, false) => "R: retry"
+                    });
+                }
+            }
             StartScreen => {
-                self.handle_input(self.input_buffer.0 as char);
-                self.input_buffer = (self.input_buffer.1, 0);
-                plot('S', BUFFER_WIDTH / 2, 0, ColorCode::new(Color::White, Color::Blue));}
+                self.consume_input();
+                self.idle += 1;
+                if self.idle >= ATTRACT_TICKS && self.status == StartScreen {
+                    self.start_demo();
+                }
+            }
+            Options | HighScores | EnterName => self.consume_input(),
+            Halted => {}
         }
     }
 
+    /// Hands the next commands to `handle_input`: the oldest queued one for a live game,
+    /// plus player 2's oldest during a two-player game, or whatever the recording says
+    /// was consumed on this tick during a replay. Live commands are recorded while the
+    /// game is running, apart from opening menu commands, which don't touch the game;
+    /// the pause menu records its own way back in with `resume`.
+    fn consume_input(&mut self) {
+        let queues = if self.status == GameOn { self.rules.players as usize } else { 1 };
+        for queue in 0..queues {
+            let command = match self.playback.as_mut() {
+                Some(playback) => playback.next_command(&self.recording, self.ticks),
+                None => {
+                    let command = self.commands[queue].pop();
+                    if let Some(command) = command {
+                        if self.status == GameOn && !matches!(command, Command::Options | Command::Select | Command::Scores | Command::Format) {
+                            self.recording.push(self.ticks, command);
+                        }
+                    }
+                    command
+                }
+            };
+            if let Some(command) = command {
+                self.handle_input(command);
+            }
+        }
+    }
+
+    /// Queues `command` for the coming ticks. During play a turn is judged against the
+    /// heading the snake will have once everything already queued has run, so that
+    /// quick presses can't add up to a reversal, and is dropped if it would not change
+    /// anything.
+    fn queue(&mut self, command: Command) {
+        let queue = command.steering().map_or(0, |(player, _)| player);
+        if let (Some((player, dir)), GameOn) = (command.steering(), self.status) {
+            let heading = self.commands[queue].iter().rev()
+                .find_map(|c| c.steering().map(|(_, d)| d))
+                .unwrap_or(self.players[player].dir);
+            if dir == heading || dir == heading.opposite() {
+                return;
+            }
+        }
+        let _ = self.commands[queue].push(command);
+    }
+
+    /// Starts a fresh game with the next seed of the session and the current settings.
     fn reset(&mut self) {
-        self.col = BUFFER_WIDTH / 4;
-        self.row = BUFFER_HEIGHT / 2;
-        self.oldcol = BUFFER_WIDTH / 4;
-        self.oldrow = BUFFER_HEIGHT / 2;
-        self.dx = 1;
-        self.dy = 0;
-        self.score = 0;
-        self.length = 0;
-        self.status = GameOn;
-        self.apple_x = 0;
-        self.apple_y = 0;
-        self.apple_effect = APPLE_STALL_TICKS * UPDATE_FREQUENCY;
-        self.countdown = UPDATE_FREQUENCY;
-        self.tail_col = BUFFER_WIDTH / 4;
-        self.tail_row = BUFFER_HEIGHT / 2;
-        self.input_buffer = (0, 0);
-        clear_screen();
-        Self::draw_frame();
-    }
-
-    fn do_a_random(&mut self) -> u32 {
-        self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.length as u64) % 256))) % (u32::max_value() as u64)) as u32;
-        self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-        let mut i: u32 = 0;
-        let mut a_random: u64 = self.seed.into();
-        while i < self.length as u32 {
-            match a_random.checked_pow(i) {
-                Some(a) => {a_random = a + self.col as u64;}
-                None => {a_random = (a_random * (i as u64)).mod_floor(&(u32::max_value() as u64))}
-            }
-            (a_random).mod_floor(&(u32::max_value() as u64));
-            i+=1;
-        }
-        plot_num(((a_random as u32).mod_floor(&(BUFFER_WIDTH as u32 - 5)) + 1) as isize, BUFFER_WIDTH / 2 + 14, 0, Pal::disp(Text));
-        return a_random as u32;
-    }
-
-    fn replace_current(&mut self) {
-        
-        let old_dx = (self.col + 2 - self.oldcol) as i32 - 2;
-        let old_dy = (self.row + 2 - self.oldrow) as i32 - 2;
-        let temp_dx = (add1::<BUFFER_WIDTH>(self.dx) as i32) - 1;
-        let temp_dy = (add1::<BUFFER_HEIGHT>(self.dy) as i32) - 1;
-        
-        self.update_location();
-        let c = match (old_dx, old_dy, temp_dx, temp_dy) {
-            (-1, 0, 0, -1) => Body(Left2Up),
-            (0, 1, 1, 0) => Body(Left2Up),
-            (1, 0, 0, 1) => Body(Right2Down),
-            (0, -1, -1, 0) => Body(Right2Down),
-            (-1, 0, 0, 1) => Body(Left2Down),
-            (0, -1, 1, 0) => Body(Left2Down),
-            (1, 0, 0, -1) => Body(Right2Up),
-            (0, 1, -1, 0) => Body(Right2Up),
-            (1, 0, 1, 0) => Body(Hori),
-            (-1, 0, -1, 0) => Body(Hori),
-            (0, 1, 0, 1) => Body(Vert),
-            (0, -1, 0, -1) => Body(Vert),
-            (_, _, _, _) => Doug('?')
+        let seed = self.seeds.next_u64();
+        self.apply_rules(self.settings);
+        self.recording = Recording::new(seed, self.rules);
+        self.playback = None;
+        self.init_game(seed);
+        self.rewind.clear();
+        self.redraw();
+        if self.rules.practice {
+            self.flash_note("PRACTICE  Bksp: rewind");
+        }
+    }
+
+    /// Plays the last game again from its recording.
+    fn start_replay(&mut self, speed: ReplaySpeed) {
+        self.apply_rules(self.recording.settings());
+        self.init_game(self.recording.seed());
+        self.rewind.clear();
+        self.redraw();
+        self.playback = Some(Playback::new(speed));
+        self.show_replay_speed(speed);
+    }
+
+    /// Loads the level `rules` asks for and sizes the board to it. A level that failed
+    /// to parse falls back to the open field; `redraw` says so on the HUD.
+    fn apply_rules(&mut self, rules: GameSettings) {
+        self.level = match self.levels.get(rules.level as usize) {
+            Some(Ok(level)) => *level,
+            _ => Level::open()
         };
-        //if(is_drawable(Sym::disp(c))){
-            plot(Sym::disp(c), self.oldcol, self.oldrow, Pal::disp(Snake));
-        /*} else {
-            clear_row(0, Color::Black);
-            clear_row(1, Color::Black);
-            clear_row(2, Color::Black);
-            clear_row(3, Color::Black);
-            clear_row(4, Color::Black);
-            clear_row(5, Color::Black);
-            plot_num(old_dx as isize, self.col, 0, Pal::disp(Text));
-            plot_num(old_dy as isize, self.col, 1, Pal::disp(Text));
-            plot_num(temp_dx as isize, self.col, 2, Pal::disp(Text));
-            plot_num(temp_dy as isize, self.col, 3, Pal::disp(Text));
-            plot_num(self.dx as isize, self.col, 4, Pal::disp(Text));
-            plot_num(self.dy as isize, self.col, 5, Pal::disp(Text));
-            self.status = Paused;
-
-        }*/
+        self.rules = self.level.fit(rules);
     }
 
-    fn update_location(&mut self) {
-        self.oldcol = self.col;
-        self.oldrow = self.row;
-        self.col = safe_add::<BUFFER_WIDTH>(self.col, self.dx);
-        self.row = safe_add::<BUFFER_HEIGHT>(self.row, self.dy);
-        match safe_peek(self.col, self.row) {
-            (Start, _) => {}
-            (Head, _) => {}
-            (Body(_), _) => {
-                self.col = self.oldcol;
-                self.row = self.oldrow;
-                self.status = GameOver;
+    /// The parse error for the level the current game asked for, if it had one.
+    fn level_error(&self) -> Option<LevelError> {
+        match self.levels.get(self.rules.level as usize) {
+            Some(Err(error)) => Some(*error),
+            _ => None
+        }
+    }
+
+    /// Puts the game state back to the start of a game played under `rules`.
+    fn init_game(&mut self, seed: u64) {
+        let growth = (self.rules.start_length as usize).saturating_sub(1);
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let (start, dir) = self.level.spawn(&self.rules, i);
+            *player = Player::new(start, dir, growth);
+        }
+        self.stats = RunStats { max_length: 1, ..RunStats::default() };
+        self.winner = None;
+        self.status = GameOn;
+        self.loaded = false;
+        self.food.clear();
+        self.boost = 0;
+        self.countdown = self.rules.move_delay();
+        self.commands.iter_mut().for_each(Queue::clear);
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        self.ticks = 0;
+    }
+
+    /// Draws the whole screen from scratch out of the game state.
+    fn redraw(&mut self) {
+        self.renderer.clear_screen();
+        self.draw_frame();
+        if let Some(error) = self.level_error() {
+            self.plot_note(error.message());
+        }
+        let foods = self.food;
+        for &food in foods.iter() {
+            self.draw_food(food);
+        }
+        for p in (0..self.rules.snakes()).filter(|&p| self.players[p].alive) {
+            let color = Pal::disp(self.pal(p));
+            let body = self.players[p].body;
+            for i in (1..body.len()).rev() {
+                let cell = body.get(i).unwrap();
+                let c = Body(self.trail_at(p, i));
+                self.renderer.plot(Sym::disp(c), cell.col(), cell.row(), color);
             }
-            (Apple, _) => {
-                self.apple_effect += APPLE_STALL_TICKS;
-                self.score += 1;
+            self.renderer.plot(Sym::disp(Head), body.head().col(), body.head().row(), color);
+        }
+        match self.status {
+            GameOver if self.rules.players > 1 => self.draw_round_result(),
+            GameOver => self.draw_stats(),
+            Paused => self.pause.open(&mut self.renderer),
+            _ => {}
+        }
+    }
+
+    fn open_options(&mut self) {
+        self.options_return = self.status;
+        self.status = Options;
+        self.options.draw(&self.settings, &mut self.renderer);
+    }
+
+    fn close_options(&mut self) {
+        if self.options_return == StartScreen {
+            self.open_title();
+        } else {
+            self.status = self.options_return;
+            self.redraw();
+        }
+    }
+
+    /// Shows the title menu. The kernel boots into it.
+    pub fn open_title(&mut self) {
+        self.status = StartScreen;
+        self.demo = false;
+        self.idle = 0;
+        self.wins = [0; MAX_PLAYERS as usize];
+        self.draw_title();
+    }
+
+    fn draw_title(&mut self) {
+        let can_replay = self.can_replay();
+        self.title.draw(&self.settings, can_replay, &mut self.renderer);
+        let text = Pal::disp(Text);
+        let end = self.renderer.plot_str("Save disk: ", 25, 23, text);
+        match self.disk {
+            DiskStatus::Missing => self.renderer.plot_str("none", end, 23, text),
+            DiskStatus::Ready { games } => {
+                let end = self.renderer.plot_num(games as isize, end, 23, text);
+                self.renderer.plot_str(" saved", end, 23, text)
             }
-            (NaN, _) => { self.col = self.oldcol; self.row = self.oldrow; 
-                self.status = GameOver; 
+            DiskStatus::Failed(error) => {
+                let end = self.renderer.plot_str(error.message(), end, 23, text);
+                if self.disk.can_format() { self.renderer.plot_str(". F: format", end, 23, text) } else { end }
             }
-            _ => {}
+        };
+    }
+
+    fn can_replay(&self) -> bool {
+        self.played && self.recording.is_complete()
+    }
+
+    /// Stops the game and puts the pause menu up over the board, Resume highlighted.
+    fn pause(&mut self) {
+        self.status = Paused;
+        self.pause = PauseMenu::default();
+        self.pause.open(&mut self.renderer);
+        self.plot_note("PAUSED  Esc: resume");
+    }
+
+    /// Takes the pause menu down and counts back into the game. A live game records
+    /// this as the `Back` that ends the pause, so a replay resumes on the same tick.
+    fn resume(&mut self) {
+        if self.playback.is_none() {
+            self.recording.push(self.ticks, Command::Back);
         }
+        self.status = Resuming;
+        self.pause.start_count(&mut self.renderer);
+        self.plot_note("");
     }
 
-    fn draw_current(&self) {
-        plot(
-            Sym::disp(Head),
-            self.col,
-            self.row,
-            Pal::disp(Snake),
-        );
+    /// Carries out the highlighted pause menu entry.
+    fn select_pause_item(&mut self) {
+        match self.pause.selected() {
+            PauseItem::Resume => self.resume(),
+            PauseItem::Restart => self.reset(),
+            PauseItem::Options => self.open_options(),
+            PauseItem::Controls => self.pause.show_controls(self.settings.keys, self.rules.players, &mut self.renderer),
+            PauseItem::Quit => self.open_title()
+        }
     }
 
-    fn find_vacant(&mut self, mut pos: (usize, usize)) -> (usize, usize) {
-        let wrap_point = pos.0;
-        let kill_point  = pos.1;
-        loop {
-            match safe_peek(pos.0, pos.1) { 
-                (Empty, a) => { 
-                    match a.background() {
-                        Color::Black => return pos, 
-                        _ => {
-                            pos = ((self.do_a_random() % (BUFFER_WIDTH as u32 - 2) + 1) as usize, (self.do_a_random() % (BUFFER_HEIGHT as u32 - 3) + 2) as usize);
-                        }
-                    }
-                }, 
-                (_, a) => {
-                    pos = (add1::<BUFFER_WIDTH>(pos.0), pos.1);
-                    if pos.0 == wrap_point {
-                        pos = (pos.0, add1::<BUFFER_HEIGHT>(pos.1));
-                        if pos.1 == kill_point {
-                            self.status = Paused;
-                            return (0, BUFFER_WIDTH - 2);
-                        }
-                    }
-                }
-            } 
-        }
-    }
-
-    fn place_apple(&mut self) {
-        let rand_x = (self.do_a_random() % (BUFFER_WIDTH as u32 - 2) + 1) as usize;
-        
-        let rand_y = (self.do_a_random() % (BUFFER_HEIGHT as u32 - 3) + 2) as usize;
-        let newpos = self.find_vacant((rand_x, rand_y));
-        self.apple_x = newpos.0;
-        self.apple_y = newpos.1;
-        
-        plot(
-            Sym::disp(Apple),
-            self.apple_x,
-            self.apple_y,
-            Pal::disp(Appl)
-        );
-    }
-
-    fn erase_tail(&mut self) {
-        match safe_peek(self.tail_col, self.tail_row) {
-            
-            (Body(a), _) => {
-                plot(' ', self.tail_col, self.tail_row, Pal::disp(EmptySpace));
-                match (a, safe_peek(add1::<BUFFER_WIDTH>(self.tail_col), self.tail_row).0) {
-                    (Hori, Body(Hori)) => { self.tail_col += 1; return () }
-                    (Left2Up, Body(Hori)) => { self.tail_col += 1; return () }
-                    (Left2Down, Body(Hori)) => { self.tail_col += 1; return () }
-                    (Hori, Body(Right2Up)) => { self.tail_col += 1; return () }
-                    (Left2Up, Body(Right2Up)) => { self.tail_col += 1; return () }
-                    (Left2Down, Body(Right2Up)) => { self.tail_col += 1; return () }
-                    (Hori, Body(Right2Down)) => { self.tail_col += 1; return () }
-                    (Left2Up, Body(Right2Down)) => { self.tail_col += 1; return () }
-                    (Left2Down, Body(Right2Down)) => { self.tail_col += 1; return () }
-                    //(_, b) => {plot(Sym::disp(b), 1, 0, Pal::disp(Appl))}
-                    _ => {}
-                }
-                match (a, safe_peek(self.tail_col, add1::<BUFFER_HEIGHT>(self.tail_row)).0) {
-                    (Vert, Body(Vert)) => { self.tail_row += 1; return ()}
-                    (Right2Down, Body(Vert)) => { self.tail_row += 1; return ()}
-                    (Left2Down, Body(Vert)) => { self.tail_row += 1; return ()}
-                    (Vert, Body(Right2Up)) => { self.tail_row += 1; return ()}
-                    (Right2Down, Body(Right2Up)) => { self.tail_row += 1; return ()}
-                    (Left2Down, Body(Right2Up)) => { self.tail_row += 1; return ()}
-                    (Vert, Body(Left2Up)) => { self.tail_row += 1; return ()}
-                    (Right2Down, Body(Left2Up)) => { self.tail_row += 1; return ()}
-                    (Left2Down, Body(Left2Up)) => { self.tail_row += 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 2, 0, Pal::disp(Appl))}
-                    _ => {}
-                }
-                match (a, safe_peek(sub1::<BUFFER_WIDTH>(self.tail_col), self.tail_row).0) {
-                    (Hori, Body(Hori)) => { self.tail_col -= 1; return ()}
-                    (Right2Up, Body(Hori)) => { self.tail_col -= 1; return ()}
-                    (Right2Down, Body(Hori)) => { self.tail_col -= 1; return ()}
-                    (Hori, Body(Left2Up)) => { self.tail_col -= 1; return ()}
-                    (Right2Up, Body(Left2Up)) => { self.tail_col -= 1; return ()}
-                    (Right2Down, Body(Left2Up)) => { self.tail_col -= 1; return ()}
-                    (Hori, Body(Left2Down)) => { self.tail_col -= 1; return ()}
-                    (Right2Up, Body(Left2Down)) => { self.tail_col -= 1; return ()}
-                    (Right2Down, Body(Left2Down)) => { self.tail_col -= 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 3, 0, Pal::disp(Appl))}
-                    _ => {}
-                }
-                match (a, safe_peek(self.tail_col, sub1::<BUFFER_WIDTH>(self.tail_row)).0) {
-                    (Vert, Body(Vert)) => { self.tail_row -= 1; return ()}
-                    (Right2Up, Body(Vert)) => { self.tail_row -= 1; return ()}
-                    (Left2Up, Body(Vert)) => { self.tail_row -= 1; return ()}
-                    (Vert, Body(Right2Down)) => { self.tail_row -= 1; return ()}
-                    (Right2Up, Body(Right2Down)) => { self.tail_row -= 1; return ()}
-                    (Left2Up, Body(Right2Down)) => { self.tail_row -= 1; return ()}
-                    (Vert, Body(Left2Down)) => { self.tail_row -= 1; return ()}
-                    (Right2Up, Body(Left2Down)) => { self.tail_row -= 1; return ()}
-                    (Left2Up, Body(Left2Down)) => { self.tail_row -= 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 4, 0, Pal::disp(Appl))}
-                    _ => {}
+    /// Carries out the highlighted title menu entry.
+    fn select_title_item(&mut self) {
+        match self.title.selected() {
+            MenuItem::Play => self.reset(),
+            MenuItem::Options => self.open_options(),
+            MenuItem::HighScores => self.open_high_scores(self.settings.walls, None),
+            MenuItem::Replay => if self.can_replay() { self.start_replay(ReplaySpeed::Normal) },
+            MenuItem::Quit => self.halt(),
+            MenuItem::Mode | MenuItem::Players => {}
+        }
+    }
+
+    /// Shows the table for `mode`, coming back to the current screen when closed.
+    fn open_high_scores(&mut self, mode: WallMode, highlight: Option<usize>) {
+        self.scores_return = self.status;
+        self.status = HighScores;
+        self.scores_shown = mode;
+        self.scores_highlight = highlight;
+        self.high_scores.draw(mode, highlight, &mut self.renderer);
+    }
+
+    fn close_high_scores(&mut self) {
+        if self.scores_return == StartScreen {
+            self.open_title();
+        } else {
+            self.status = self.scores_return;
+            self.redraw();
+        }
+    }
+
+    /// Lets Left/Right flip through the other modes' tables.
+    fn cycle_high_scores(&mut self, up: bool) {
+        self.scores_shown = self.scores_shown.cycle(up);
+        self.scores_highlight = None;
+        self.high_scores.draw(self.scores_shown, None, &mut self.renderer);
+    }
+
+    fn final_score(&self) -> u16 {
+        self.score().min(u16::MAX as usize) as u16
+    }
+
+    /// Takes a key typed on the name entry screen.
+    fn type_initial(&mut self, c: char) {
+        match c {
+            '\n' if !self.name.is_empty() => {
+                self.save_high_score();
+                return;
+            }
+            '\u{8}' => self.name.pop(),
+            '\u{1B}' => {
+                self.queue(Command::Back);
+                return;
+            }
+            c => self.name.push(c)
+        }
+        self.name.draw(self.final_score(), &mut self.renderer);
+    }
+
+    /// Files the entered initials and shows where they landed. Closing the table goes
+    /// back to the game-over screen.
+    fn save_high_score(&mut self) {
+        let entry = self.name.entry(self.final_score());
+        let rank = self.high_scores.table_mut(self.rules.walls).insert(entry);
+        self.best_rank = rank;
+        self.unsaved = true;
+        self.last_initials = entry.initials;
+        self.unlogged = true;
+        self.status = GameOver;
+        self.open_high_scores(self.rules.walls, rank);
+    }
+
+    /// Stops the game for good; the kernel halts once it sees `Halted`.
+    fn halt(&mut self) {
+        self.status = Halted;
+        self.renderer.clear_screen();
+        self.renderer.plot_str("It is now safe to turn off your computer.", 19, 12, Pal::disp(Text));
+    }
+
+    /// Ends the game and puts up its results, asking for initials first if a live game
+    /// made the table. A live game is logged once the initials are settled. Two-player
+    /// rounds skip the table and show who won instead. Practice games never make the
+    /// table, and neither do games a save was loaded into, so saving before a risky move
+    /// can't buy a score. Practice rounds don't count towards the wins. A live game's
+    /// quick-saves go with it, so a run that has been scored can't be loaded back for
+    /// another go.
+    fn end_game(&mut self) {
+        self.status = GameOver;
+        if self.demo {
+            return;
+        }
+        self.played = true;
+        if self.playback.is_none() {
+            self.saves = [None; SAVE_SLOTS];
+        }
+        if self.rules.players > 1 {
+            if self.playback.is_none() {
+                if let Some(winner) = self.winner.filter(|_| !self.rules.practice) {
+                    self.wins[winner] = self.wins[winner].saturating_add(1);
                 }
-                //plot(Sym::disp(Body(a)), 0, 0, Pal::disp(Appl))
-            }
-            (Start, _) => {
-                plot(' ', self.tail_col, self.tail_row, Pal::disp(EmptySpace));
-                match safe_peek(add1::<BUFFER_WIDTH>(self.tail_col), self.tail_row).0 {
-                    Body(Hori) => { self.tail_col += 1; return () }
-                    Body(Right2Up) => { self.tail_col += 1; return () }
-                    Body(Right2Down) => { self.tail_col += 1; return () }
-                    //(_, b) => {plot(Sym::disp(b), 1, 0, Pal::disp(Appl))}
-                    _ => {}
+                self.last_initials = [0; INITIALS];
+                self.unlogged = true;
+            }
+            self.draw_round_result();
+            return;
+        }
+        if self.playback.is_some() {
+            self.draw_stats();
+            return;
+        }
+        self.best_rank = None;
+        self.draw_stats();
+        self.last_initials = [0; INITIALS];
+        if !self.rules.practice && !self.loaded && self.high_scores.table(self.rules.walls).qualifies(self.final_score()) {
+            self.status = EnterName;
+            self.name = NameEntry::default();
+            self.name.draw(self.final_score(), &mut self.renderer);
+        } else {
+            self.unlogged = true;
+        }
+    }
+
+    /// Plays a game on autopilot behind the title, with the default settings, until a
+    /// key is pressed. The last real game's recording is left alone.
+    fn start_demo(&mut self) {
+        let seed = self.seeds.next_u64();
+        self.apply_rules(GameSettings::default());
+        self.init_game(seed);
+        self.rewind.clear();
+        self.demo = true;
+        self.redraw();
+        self.plot_note("DEMO  press any key");
+    }
+
+    fn steer_demo(&mut self) {
+        let board = self.board();
+        if let Some(dir) = ai::autopilot(&board, self.players(), 0, self.rules.growth as usize, self.food.apple()) {
+            self.handle_input(Command::Steer(0, dir));
+        }
+    }
+
+    /// The board as the autopilot sees it, with poison to be kept away from.
+    fn board(&self) -> ai::Board<'_> {
+        let mut board = ai::Board::new(&self.rules, &self.level, self.players());
+        for food in self.food.iter().filter(|f| f.kind == FoodKind::Poison) {
+            board.block(food.cell);
+        }
+        board
+    }
+
+    /// Has every computer opponent pick its way for the coming move.
+    fn steer_opponents(&mut self) {
+        let board = self.board();
+        let mut turns = [None; MAX_SNAKES];
+        for p in (self.rules.players as usize..self.rules.snakes()).filter(|&p| self.players[p].alive) {
+            turns[p] = ai::steer(self.rules.skill, &board, self.players(), p, self.food.apple());
+        }
+        for (p, dir) in turns.into_iter().enumerate() {
+            if let Some(dir) = dir {
+                self.handle_input(Command::Steer(p, dir));
+            }
+        }
+    }
+
+    /// Moves every head one cell along its `dir`, then settles growth, food and
+    /// collisions against the in-memory bodies before anything is drawn. All snakes
+    /// move at once: a head that runs into a wall, any body, poison, or the cell
+    /// another head is moving to, crashes, unless its power-ups see it through. A
+    /// player's crash starts the death sequence; a crashed opponent just leaves the
+    /// board.
+    fn update_location(&mut self) {
+        let (humans, count) = (self.rules.players as usize, self.rules.snakes());
+        let alive = |p: &usize| self.players[*p].alive;
+        let mut next = [None; MAX_SNAKES];
+        let mut cause = [None; MAX_SNAKES];
+        for p in (0..count).filter(alive) {
+            let player = &self.players[p];
+            let ghost = player.effects.has(Power::Ghost);
+            let target = self.rules.next_cell(player.body.head(), player.dir);
+            cause[p] = match target {
+                None => Some(Cause::Wall),
+                Some(cell) if self.level.is_wall(&self.rules, cell) => Some(Cause::Wall),
+                Some(cell) => (0..count).filter(alive)
+                    .find(|&q| {
+                        let other = &self.players[q];
+                        !(q == p && ghost) && other.body.blocks(cell, other.is_growing())
+                    })
+                    .map(|q| if q == p { Cause::OwnBody } else { Cause::Opponent })
+                    .or(self.food.at(cell).filter(|f| f.kind == FoodKind::Poison).map(|_| Cause::Poison))
+            };
+            next[p] = target.filter(|_| cause[p].is_none());
+        }
+        // A shield takes a knock from a wall, a body or another head, and the snake sits
+        // this move out, leaving the cell it was heading for to the other snake.
+        let mut held = [false; MAX_SNAKES];
+        for p in (0..count).filter(|&p| cause[p].is_some_and(|c| c != Cause::Poison)) {
+            held[p] = self.players[p].effects.use_shield();
+        }
+        let head_on: [bool; MAX_SNAKES] = core::array::from_fn(|p| {
+            next[p].is_some_and(|cell| (0..count).any(|q| q != p && next[q] == Some(cell)))
+        });
+        for p in (0..count).filter(|&p| head_on[p]) {
+            if self.players[p].effects.use_shield() {
+                held[p] = true;
+                next[p] = None;
+            }
+        }
+        let mut crashed = [false; MAX_SNAKES];
+        for p in (0..count).filter(|&p| self.players[p].alive) {
+            crashed[p] = match next[p] {
+                Some(cell) => (0..count).any(|q| q != p && next[q] == Some(cell)),
+                None => !held[p]
+            };
+            if crashed[p] && next[p].is_some() {
+                cause[p] = Some(Cause::Opponent);
+            }
+        }
+        if crashed[..humans].contains(&true) {
+            self.stats.cause = cause[0].filter(|_| crashed[0]);
+            self.winner = (0..humans).find(|&p| !crashed[p]);
+            let mut dead = [false; MAX_PLAYERS as usize];
+            dead[..humans].copy_from_slice(&crashed[..humans]);
+            self.death = DeathScene::new(self.players(), dead);
+            self.status = Death;
+            return;
+        }
+        for p in (humans..count).filter(|&p| crashed[p]) {
+            self.knock_out(p);
+        }
+
+        let mut vacated = [None; MAX_SNAKES];
+        let mut eaten = [None; MAX_SNAKES];
+        for (p, &next) in next[..count].iter().enumerate() {
+            let Some(next) = next.filter(|_| !crashed[p]) else { continue };
+            let player = &mut self.players[p];
+            player.body.push_head(next);
+            if p == 0 {
+                self.stats.moves += 1;
+                self.stats.max_length = self.stats.max_length.max(player.body.len());
+            }
+            vacated[p] = if player.is_growing() {
+                player.growth -= 1;
+                None
+            } else {
+                player.body.pop_tail()
+            };
+
+            eaten[p] = self.food.take(next);
+        }
+        // Blank every vacated tail first, since another head may just have taken its
+        // place. A ghost's tail can leave a cell its body still crosses.
+        for tail in vacated.into_iter().flatten() {
+            if !self.players().iter().any(|p| p.alive && p.body.contains(tail)) {
+                self.renderer.plot(Sym::disp(Empty), tail.col(), tail.row(), Pal::disp(EmptySpace));
+            }
+        }
+        for (p, food) in eaten.into_iter().enumerate() {
+            if let Some(food) = food {
+                self.eat(p, food.kind);
+            }
+        }
+        for player in self.players[..count].iter_mut() {
+            player.effects.wear();
+        }
+        self.pull_food();
+        for p in 0..count {
+            if self.players[p].alive {
+                self.draw_current(p);
+            }
+        }
+    }
+
+    /// What `food` does for the snake of `player` that just ate it. An apple may bring
+    /// a bonus food out with the next one.
+    fn eat(&mut self, player: usize, food: FoodKind) {
+        if player == 0 && food.feeds() {
+            self.stats.apples += 1;
+        }
+        let snake = &mut self.players[player];
+        snake.score += food.points();
+        if food.feeds() {
+            snake.growth += self.rules.growth as usize;
+        }
+        match food {
+            FoodKind::Apple => if let Some(bonus) = food::roll_bonus(&mut self.rng) {
+                self.place_food(bonus);
+            },
+            FoodKind::Shrink => {
+                snake.growth = 0;
+                for _ in 0..food::SHRINK_SEGMENTS {
+                    let Some(tail) = snake.body.pop_tail() else { break };
+                    self.renderer.plot(Sym::disp(Empty), tail.col(), tail.row(), Pal::disp(EmptySpace));
                 }
-                match safe_peek(self.tail_col, add1::<BUFFER_HEIGHT>(self.tail_row)).0 {
-                    Body(Vert) => { self.tail_row += 1; return ()}
-                    Body(Right2Up) => { self.tail_row += 1; return ()}
-                    Body(Left2Up) => { self.tail_row += 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 2, 0, Pal::disp(Appl))}
-                    _ => {}
+            }
+            FoodKind::Speed => self.boost = food::SPEED_MOVES,
+            FoodKind::Power(power) => snake.effects.grant(power),
+            FoodKind::Golden | FoodKind::Poison => {}
+        }
+    }
+
+    /// Draws food within reach of a magnet one cell closer to its head, if the cell
+    /// there is free. Only food worth eating is pulled.
+    fn pull_food(&mut self) {
+        for p in 0..self.rules.snakes() {
+            let player = &self.players[p];
+            if !player.alive || !player.effects.has(Power::Magnet) {
+                continue;
+            }
+            let head = player.body.head();
+            let foods = self.food;
+            for food in foods.iter().filter(|f| f.kind.feeds()) {
+                let (dx, dy) = (head.col() as isize - food.cell.col() as isize, head.row() as isize - food.cell.row() as isize);
+                let reach = dx.unsigned_abs() + dy.unsigned_abs();
+                if reach <= 1 || reach > power::MAGNET_RANGE {
+                    continue;
                 }
-                match safe_peek(sub1::<BUFFER_WIDTH>(self.tail_col), self.tail_row).0 {
-                    Body(Hori) => { self.tail_col -= 1; return ()}
-                    Body(Left2Up) => { self.tail_col -= 1; return ()}
-                    Body(Left2Down) => { self.tail_col -= 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 3, 0, Pal::disp(Appl))}
-                    _ => {}
+                let dir = match (dx.abs() >= dy.abs(), dx > 0, dy > 0) {
+                    (true, true, _) => Dir::Right,
+                    (true, false, _) => Dir::Left,
+                    (false, _, true) => Dir::Down,
+                    (false, _, false) => Dir::Up
+                };
+                let to = food.cell.step(dir);
+                if !self.is_vacant(to) {
+                    continue;
                 }
-                match safe_peek(self.tail_col, sub1::<BUFFER_WIDTH>(self.tail_row)).0 {
-                    Body(Vert) => { self.tail_row -= 1; return ()}
-                    Body(Right2Down) => { self.tail_row -= 1; return ()}
-                    Body(Left2Down) => { self.tail_row -= 1; return ()}
-                    //(_, b) => {plot(Sym::disp(b), 4, 0, Pal::disp(Appl))}
-                    _ => {}
+                if let Some(moved) = self.food.shift(food.cell, to) {
+                    self.renderer.plot(Sym::disp(Empty), food.cell.col(), food.cell.row(), Pal::disp(EmptySpace));
+                    self.draw_food(moved);
                 }
-                //plot(Sym::disp(Body(a)), 0, 0, Pal::disp(Appl))
             }
-            _ => {}
+        }
+    }
+
+    /// Clears away bonus foods that have been out too long.
+    fn wither_food(&mut self) {
+        for cell in self.food.wither().into_iter().flatten() {
+            self.renderer.plot(Sym::disp(Empty), cell.col(), cell.row(), Pal::disp(EmptySpace));
+        }
+    }
+
+    /// Ticks until the next move: the settings' delay, doubled while any snake is
+    /// slowed and halved while a speed apple lasts.
+    fn move_delay(&mut self) -> usize {
+        let mut delay = self.rules.move_delay();
+        if self.players().iter().any(|p| p.alive && p.effects.has(Power::Slow)) {
+            delay = delay * 2 + 1;
+        }
+        if self.boost > 0 {
+            self.boost -= 1;
+            delay /= 2;
+        }
+        delay
+    }
+
+    /// Takes a crashed opponent off the board.
+    fn knock_out(&mut self, p: usize) {
+        self.players[p].alive = false;
+        for cell in self.players[p].body.iter() {
+            self.renderer.plot(Sym::disp(Empty), cell.col(), cell.row(), Pal::disp(EmptySpace));
+        }
+    }
+
+    /// Projects `player`'s latest move onto the screen: the old head becomes a body
+    /// segment and the new head is drawn.
+    fn draw_current(&mut self, player: usize) {
+        let color = Pal::disp(self.pal(player));
+        if let Some(neck) = self.players[player].body.get(1) {
+            let c = Body(self.trail_at(player, 1));
+            self.renderer.plot(Sym::disp(c), neck.col(), neck.row(), color);
+        }
+        let head = self.players[player].body.head();
+        self.renderer.plot(Sym::disp(Head), head.col(), head.row(), color);
+    }
+
+    /// The glyph for segment `i` of `player`'s snake, joining it to the segments on
+    /// either side.
+    fn trail_at(&self, player: usize, i: usize) -> BodyTrail {
+        let Player { body, dir, .. } = &self.players[player];
+        let here = body.get(i).unwrap();
+        let toward_head = i.checked_sub(1).and_then(|j| body.get(j)).and_then(|c| here.dir_to(c));
+        let toward_tail = body.get(i + 1).and_then(|c| here.dir_to(c));
+        match (toward_head, toward_tail) {
+            (Some(a), Some(b)) => BodyTrail::joining(a, b),
+            (Some(a), None) | (None, Some(a)) => BodyTrail::joining(a, a.opposite()),
+            (None, None) => BodyTrail::joining(*dir, dir.opposite())
+        }
+    }
+
+    fn is_vacant(&self, cell: Cell) -> bool {
+        self.rules.in_playfield(cell)
+            && !self.players().iter().any(|p| p.alive && p.body.contains(cell))
+            && self.food.at(cell).is_none()
+            && !self.level.is_wall(&self.rules, cell)
+    }
+
+    /// The first free playfield cell at or after playfield index `start` in reading order,
+    /// keeping to the level's apple zones if `zoned`.
+    fn find_vacant(&self, start: usize, zoned: bool) -> Option<Cell> {
+        let (w, h) = self.rules.playfield_size();
+        (start..start + w * h)
+            .map(|i| self.rules.playfield_cell(i))
+            .find(|cell| self.is_vacant(*cell) && (!zoned || self.level.in_zone(&self.rules, *cell)))
+    }
+
+    /// Grows a `kind` in one of the level's zones, or anywhere free once they are full.
+    /// A board with no room left for an apple pauses the game; a bonus food just doesn't
+    /// grow.
+    fn place_food(&mut self, kind: FoodKind) {
+        if self.food.is_full() {
+            return;
+        }
+        let (w, h) = self.rules.playfield_size();
+        let start = self.rng.gen_range(0..w * h);
+        match self.find_vacant(start, true).or_else(|| self.find_vacant(start, false)) {
+            Some(cell) => {
+                let food = Food::new(cell, kind);
+                self.food.add(food);
+                self.draw_food(food);
+            }
+            None if kind == FoodKind::Apple => self.pause(),
+            None => {}
+        }
+    }
+
+    fn draw_food(&mut self, food: Food) {
+        self.renderer.plot(Sym::disp(Sym::Food(food.kind)), food.cell.col(), food.cell.row(), Pal::disp(Pal::Food(food.kind)));
+    }
+
+    pub fn draw_frame(&mut self) {
+        let right = self.rules.width as usize - 1;
+        let bottom = self.rules.height as usize;
+        self.hud.invalidate();
+        self.note = "";
+        self.update_hud();
+        if self.rules.walls.has_border() {
+            // A border the snake can pass through is drawn in a softer colour.
+            let wall = Pal::disp(if self.rules.walls == WallMode::Wrap { Warp } else { Wall });
+            for i in num::range(0, right + 1) {
+                self.renderer.plot('=', i, 1, wall);
+                self.renderer.plot('=', i, bottom, wall);
+            }
+            for i in num::range(1, bottom + 1) {
+                self.renderer.plot('|', 0, i, wall);
+                self.renderer.plot('|', right, i, wall);
+            }
+            self.renderer.plot('r', 0, 1, wall);
+            self.renderer.plot(';', right, 1, wall);
+            self.renderer.plot('L', 0, bottom, wall);
+            self.renderer.plot('J', right, bottom, wall);
+        }
+        for cell in self.level.walls(&self.rules) {
+            self.renderer.plot('#', cell.col(), cell.row(), Pal::disp(Wall));
+        }
+    }
+
+    /// Brings the HUD up to date with the game.
+    fn update_hud(&mut self) {
+        let slow = self.players().iter().any(|p| p.alive && p.effects.has(Power::Slow));
+        let best = self.high_scores.table(self.rules.walls).entries().first().map_or(0, |e| e.score as usize);
+        let fields = HudFields {
+            players: self.rules.players,
+            scores: [self.players[0].score, self.players[1].score],
+            best: best.max(self.score()),
+            length: self.body().len(),
+            level: self.rules.level,
+            speed: self.rules.speed,
+            pace: (self.boost > 0).cmp(&slow),
+            seconds: self.stats.seconds(),
+            effects: if self.rules.players > 1 { Effects::default() } else { self.players[0].effects },
+            note: self.note
         };
+        self.hud.draw(fields, &mut self.renderer);
     }
 
-    pub fn draw_frame() {
-        for i in num::range(0, BUFFER_WIDTH) {
-            plot(' ', i, 0, ColorCode::new(Color::Blue, Color::Blue));
-            plot('=', i, 1, Pal::disp(Wall));
-            plot('=', i, BUFFER_HEIGHT - 1, Pal::disp(Wall));
+    /// Puts the outcome of a two-player round up in the middle of the board.
+    fn draw_round_result(&mut self) {
+        let text = Pal::disp(Text);
+        let panel = Rect::new(BUFFER_WIDTH / 2 - 14, 9, 28, 6);
+        let (left, top) = (panel.left, panel.top);
+        draw_box(&mut self.renderer, panel, Frame::Double, text);
+        let headline = match self.winner {
+            Some(0) => "PLAYER 1 WINS!",
+            Some(_) => "PLAYER 2 WINS!",
+            None => "DRAW!"
+        };
+        draw_str(&mut self.renderer, headline, panel.middle(), top + 1, Align::Center, text);
+        for (p, col) in [(0, left + 4), (1, left + 16)] {
+            let end = self.renderer.plot_str("P", col, top + 3, text);
+            let end = self.renderer.plot_num(p as isize + 1, end, top + 3, text);
+            let end = self.renderer.plot_str(": ", end, top + 3, text);
+            self.renderer.plot_num(self.players[p].score as isize, end, top + 3, text);
         }
-        for i in num::range(0, BUFFER_HEIGHT) {
-            plot('|', 0, i, Pal::disp(Wall));
-            plot('|', BUFFER_WIDTH - 1, i, Pal::disp(Wall));
+        let end = self.renderer.plot_str("Rounds won: ", left + 6, top + 4, text);
+        let end = self.renderer.plot_num(self.wins[0] as isize, end, top + 4, text);
+        let end = self.renderer.plot_str(" - ", end, top + 4, text);
+        self.renderer.plot_num(self.wins[1] as isize, end, top + 4, text);
+    }
+
+    /// Puts player 1's results up over the board.
+    fn draw_stats(&mut self) {
+        let can_replay = self.recording.is_complete();
+        self.stats.draw(self.score(), self.seed, self.best_rank, can_replay, &mut self.renderer);
+    }
+
+    /// Puts a short message up on the right of the HUD, or takes it down if empty.
+    fn plot_note(&mut self, note: &'static str) {
+        self.note = note;
+        self.note_left = 0;
+        self.update_hud();
+    }
+
+    /// Puts up a message that comes down by itself after `FLASH_TICKS` of play.
+    fn flash_note(&mut self, note: &'static str) {
+        self.plot_note(note);
+        self.note_left = FLASH_TICKS;
+    }
+
+    /// F5: keeps the game in progress in the current slot.
+    fn quick_save(&mut self) {
+        if let Some(snapshot) = self.snapshot() {
+            self.saves[self.save_slot] = Some(snapshot);
+            self.flash_note(["Saved to slot 1", "Saved to slot 2", "Saved to slot 3"][self.save_slot]);
         }
-        plot('r', 0, 1, Pal::disp(Wall));
-        plot(';', BUFFER_WIDTH - 1, 1, Pal::disp(Wall));
-        plot('L', 0, BUFFER_HEIGHT - 1, Pal::disp(Wall));
-        plot('J', BUFFER_WIDTH - 1, BUFFER_HEIGHT - 1, Pal::disp(Wall));
+    }
 
-        plot('S', 2, 0, Pal::disp(Text));
-        plot('C', 3, 0, Pal::disp(Text));
-        plot('O', 4, 0, Pal::disp(Text));
-        plot('R', 5, 0, Pal::disp(Text));
-        plot('E', 6, 0, Pal::disp(Text));
-        plot(':', 7, 0, Pal::disp(Text));
+    /// F9: picks the game in the current slot back up, counting in as after a pause.
+    /// Only a game still in progress can be swapped for a save.
+    fn quick_load(&mut self) {
+        if !matches!(self.status, GameOn | Paused | Resuming) {
+            return;
+        }
+        let Some(snapshot) = self.saves[self.save_slot] else {
+            self.flash_note(["Slot 1 is empty", "Slot 2 is empty", "Slot 3 is empty"][self.save_slot]);
+            return;
+        };
+        if let Err(error) = self.restore(&snapshot) {
+            self.flash_note(error.message());
+            return;
+        }
+        self.rewind.clear();
+        self.status = Resuming;
+        self.pause.start_count(&mut self.renderer);
+        self.flash_note(["Loaded slot 1", "Loaded slot 2", "Loaded slot 3"][self.save_slot]);
+    }
+
+    /// Backspace in a practice game: starts winding it back a move at a time, and keeps
+    /// it going while the key repeats. Once the key is let go, `advance` counts back
+    /// into play from wherever the game got to. A game that has ended stays ended, since
+    /// it has already been logged.
+    fn hold_rewind(&mut self) {
+        if !self.rules.practice || !matches!(self.status, GameOn | Paused | Resuming | Rewinding) {
+            return;
+        }
+        if self.status != Rewinding {
+            if self.rewind.is_empty() {
+                return;
+            }
+            self.status = Rewinding;
+        }
+        self.rewind_steps = (self.rewind_steps + 1).min(2);
+        self.rewind_held = RELEASE_TICKS;
+    }
+
+    /// Puts the game back as it was a move earlier. At the oldest move kept it stays put.
+    fn step_back(&mut self) {
+        let Some(snapshot) = self.rewind.pop() else { return };
+        if self.restore(&snapshot).is_ok() {
+            self.status = Rewinding;
+            self.plot_note("REWIND  let go to play");
+        }
+    }
+
+    /// F6: moves F5 and F9 on to the next slot.
+    fn next_save_slot(&mut self) {
+        self.save_slot = (self.save_slot + 1) % SAVE_SLOTS;
+        self.flash_note(["Slot 1: F5 save F9 load", "Slot 2: F5 save F9 load", "Slot 3: F5 save F9 load"][self.save_slot]);
+    }
+
+    fn show_replay_speed(&mut self, speed: ReplaySpeed) {
+        self.plot_note(match speed {
+            ReplaySpeed::Normal => "REPLAY x1  1/2/3: speed",
+            ReplaySpeed::Fast => "REPLAY x4  1/2/3: speed",
+            ReplaySpeed::Step => "REPLAY step  space: next"
+        });
     }
 
     pub fn key(&mut self, key: DecodedKey) {
+        self.idle = 0;
+        if self.demo {
+            self.open_title();
+            return;
+        }
+        if self.status == Death {
+            return;
+        }
+        if self.playback.is_some() {
+            self.control_replay(key);
+            return;
+        }
         match key {
             DecodedKey::RawKey(code) => self.handle_raw(code),
             DecodedKey::Unicode(c) => self.handle_unicode(c),
         }
     }
 
+    /// The arrows steer player 2 during a two-player game and player 1 otherwise. The
+    /// quick-save keys act straight away, since they aren't part of the game's inputs.
     fn handle_raw(&mut self, key: KeyCode) {
+        let turn = |dir| if self.status == GameOn && self.rules.players > 1 { Command::Steer(1, dir) } else { Command::Turn(dir) };
         match key {
-            KeyCode::ArrowLeft => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                
-                if self.input_buffer.0 == 0 {self.input_buffer = ('a' as u8, 0)}
-                else {self.input_buffer = (self.input_buffer.0, 'a' as u8)}
-            }
-            KeyCode::ArrowRight => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                
-                if self.input_buffer.0 == 0 {self.input_buffer = ('d' as u8, 0)}
-                else {self.input_buffer = (self.input_buffer.0, 'd' as u8)}
-            }
-            KeyCode::ArrowUp => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-
-                if self.input_buffer.0 == 0 {self.input_buffer = ('w' as u8, 0)}
-                else {self.input_buffer = (self.input_buffer.0, 'w' as u8)}
-            }
-            KeyCode::ArrowDown => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                
-                if self.input_buffer.0 == 0 {self.input_buffer = ('s' as u8, 0)}
-                else {self.input_buffer = (self.input_buffer.0, 's' as u8)}
-            }
-            KeyCode::Escape => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                match self.status {
-                    GameOn => self.status = Paused,
-                    Paused => self.status = GameOn,
-                    GameOver => self.status = StartScreen,
-                    _ => {}
-                }
-            }
+            KeyCode::ArrowLeft => self.queue(turn(Dir::Left)),
+            KeyCode::ArrowRight => self.queue(turn(Dir::Right)),
+            KeyCode::ArrowUp => self.queue(turn(Dir::Up)),
+            KeyCode::ArrowDown => self.queue(turn(Dir::Down)),
+            KeyCode::Escape => self.queue(Command::Back),
+            KeyCode::F5 => self.quick_save(),
+            KeyCode::F6 => self.next_save_slot(),
+            KeyCode::F9 => self.quick_load(),
             _ => {}
         }
     }
 
+    /// While a replay runs the keyboard drives the playback, not the snake.
+    fn control_replay(&mut self, key: DecodedKey) {
+        let speed = match key {
+            DecodedKey::Unicode('1') => ReplaySpeed::Normal,
+            DecodedKey::Unicode('2') => ReplaySpeed::Fast,
+            DecodedKey::Unicode('3') => ReplaySpeed::Step,
+            DecodedKey::Unicode(' ') => {
+                if let Some(playback) = self.playback.as_mut() { playback.steps_requested += 1 }
+                return;
+            }
+            DecodedKey::Unicode('\u{1B}') | DecodedKey::RawKey(KeyCode::Escape) => {
+                self.playback = None;
+                self.status = GameOver;
+                return;
+            }
+            _ => return
+        };
+        if let Some(playback) = self.playback.as_mut() { playback.speed = speed }
+        self.show_replay_speed(speed);
+    }
+
+    /// Backspace rewinds straight away rather than waiting in the queue, since winding
+    /// back isn't part of the game's inputs.
     fn handle_unicode(&mut self, key: char) {
-        if self.input_buffer.0 == 0 {self.input_buffer = (key as u8, 0)}
-        else { self.input_buffer = (self.input_buffer.0, key as u8) }
+        if self.status == EnterName {
+            self.type_initial(key);
+            return;
+        }
+        if key == '\u{8}' {
+            self.hold_rewind();
+            return;
+        }
+        if let Some(command) = Command::from_char(key, self.settings.keys) {
+            self.queue(command);
+        }
     }
 
-    fn handle_input(&mut self, key: char) {
-        match key {
-            'a' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                if self.dx != 1 && self.status == GameOn {
-                    self.dx = BUFFER_WIDTH - 1;
-                    self.dy = 0; 
-                }
+    /// The colour snake `player` is drawn in: each player's own, and one shared by
+    /// every computer opponent.
+    fn pal(&self, player: usize) -> Pal {
+        match player {
+            0 => Snake,
+            p if p < self.rules.players as usize => Snake2,
+            _ => Rival
+        }
+    }
+
+    fn steer(&mut self, player: usize, dir: Dir) {
+        let snake = &mut self.players[player];
+        if dir != snake.dir && dir != snake.dir.opposite() {
+            snake.dir = dir;
+            if player == 0 {
+                self.stats.turns += 1;
             }
-            'd' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                if self.dx != BUFFER_WIDTH - 1 && self.status == GameOn  {
-                    self.dx = 1;
-                    self.dy = 0; 
+        }
+    }
+
+    fn handle_input(&mut self, command: Command) {
+        match command {
+            Command::Turn(dir) => {
+                match self.status {
+                    GameOn => self.steer(0, dir),
+                    Paused if self.pause.showing_controls() => self.pause.hide_controls(&mut self.renderer),
+                    Paused => self.pause.handle(dir, &mut self.renderer),
+                    Options => {
+                        let before = self.settings;
+                        self.options.handle(dir, &mut self.settings);
+                        self.unsaved |= self.settings != before;
+                        self.options.draw(&self.settings, &mut self.renderer);
+                    }
+                    StartScreen => {
+                        let before = self.settings;
+                        self.title.handle(dir, &mut self.settings);
+                        self.unsaved |= self.settings != before;
+                        self.draw_title();
+                    }
+                    HighScores if matches!(dir, Dir::Left | Dir::Right) => self.cycle_high_scores(dir == Dir::Right),
+                    _ => {}
                 }
             }
-            'w' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                if self.dy != 1 && self.status == GameOn  {
-                    self.dy = sub1::<BUFFER_HEIGHT>(0);
-                    self.dx = 0; 
+            Command::Steer(player, dir) => if self.status == GameOn { self.steer(player, dir) },
+            Command::Back => {
+                match self.status {
+                    GameOn => self.pause(),
+                    Paused if self.pause.showing_controls() => self.pause.hide_controls(&mut self.renderer),
+                    Paused => self.resume(),
+                    GameOver => self.open_title(),
+                    HighScores => self.close_high_scores(),
+                    EnterName => {
+                        self.status = GameOver;
+                        self.unlogged = true;
+                        self.redraw();
+                    }
+                    Options => self.close_options(),
+                    _ => {}
                 }
             }
-            's' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
-                if self.dy != BUFFER_HEIGHT - 1 && self.status == GameOn  {
-                    self.dy = 1;
-                    self.dx = 0; 
+            Command::Options => {
+                if matches!(self.status, Paused | GameOver | StartScreen) {
+                    self.open_options();
                 }
             }
-            '\u{1B}' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
+            Command::Restart => {
                 match self.status {
-                    GameOn => self.status = Paused,
-                    Paused => self.status = GameOn,
-                    GameOver => self.status = StartScreen,
+                    GameOver => self.reset(),
+                    Paused => self.reset(),
                     _ => {}
                 }
             }
-            'r' => {
-                self.seed = (((self.seed as u64) + ((self.seed as u64) * ((self.seed as u64) % 256))) % (u32::max_value() as u64)) as u32;
+            Command::Replay => {
+                if self.status == GameOver && self.recording.is_complete() {
+                    self.start_replay(ReplaySpeed::Normal);
+                }
+            }
+            Command::Select => {
                 match self.status {
-                    GameOver => self.reset(),
-                    Paused => self.reset(),
+                    StartScreen => self.select_title_item(),
+                    Paused if self.pause.showing_controls() => self.pause.hide_controls(&mut self.renderer),
+                    Paused => self.select_pause_item(),
+                    HighScores => self.close_high_scores(),
                     _ => {}
                 }
             }
-            a => self.seed = (((self.seed as u64) + (a as u8 as u64)) % (u32::max_value() as u64)) as u32
+            Command::Scores => {
+                if self.status == GameOver {
+                    self.open_high_scores(self.rules.walls, None);
+                }
+            }
+            Command::Format => {
+                if self.status == StartScreen && self.disk.can_format() {
+                    self.format_requested = true;
+                }
+            }
         }
     }
 }
@@ -528,16 +1453,28 @@ impl SnakeDriver {
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
 pub enum Status {
     GameOn,
+    /// The pause menu is up over the board.
     Paused,
+    /// Counting back into the game after a pause; nothing moves yet.
+    Resuming,
+    /// Backspace is held in a practice game, which steps back a move per press.
+    Rewinding,
+    /// A player has crashed and the death sequence is playing; keys are ignored.
     Death,
     GameOver,
-    StartScreen
+    StartScreen,
+    Options,
+    HighScores,
+    /// Typing initials in for the high score table.
+    EnterName,
+    /// Quit from the title menu; nothing runs any more.
+    Halted
 }
 
 #[repr(u8)]
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
 pub enum Sym {
-    Body(BodyTrail), Head, Apple, Doug(char), Start, NaN, Empty
+    Body(BodyTrail), Head, Food(FoodKind), Doug(char), Start, NaN, Empty
 }
 
 impl Sym {
@@ -550,34 +1487,26 @@ impl Sym {
             Body(Left2Up) => 'L',
             Body(Right2Down) => ';',
             Body(Left2Down) => 'r',
-            Apple => '&',
+            Sym::Food(FoodKind::Apple) => '&',
+            Sym::Food(FoodKind::Golden) => '$',
+            Sym::Food(FoodKind::Poison) => '%',
+            Sym::Food(FoodKind::Shrink) => '-',
+            Sym::Food(FoodKind::Speed) => '*',
+            Sym::Food(FoodKind::Power(Power::Ghost)) => '@',
+            Sym::Food(FoodKind::Power(Power::Slow)) => '~',
+            Sym::Food(FoodKind::Power(Power::Shield)) => '+',
+            Sym::Food(FoodKind::Power(Power::Magnet)) => 'U',
             Doug(c) => c,
             Start => '?',
             Empty => ' ',
             _ => ' '
         }
     }
-
-    fn from(c: char) -> Sym {
-        match c {
-            '0' => Head,
-            '?' => Start,
-            '=' => Body(Hori),
-            '|' => Body(Vert),
-            'J' => Body(Right2Up),
-            'L' => Body(Left2Up),
-            ';' => Body(Right2Down),
-            'r' => Body(Left2Down),
-            '&' => Apple,
-            ' ' => Empty,
-            _ => NaN
-        }
-    }
 }
 
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
 pub enum Pal {
-    Snake, Appl, Wall, Text, EmptySpace
+    Snake, Snake2, Rival, Food(FoodKind), Wall, Warp, Text, Highlight, EmptySpace
 }
 
 
@@ -585,12 +1514,26 @@ impl Pal {
     fn disp(self) -> ColorCode {
         match self {
             Snake => ColorCode::new(Color::Cyan, Color::Black),
+            Snake2 => ColorCode::new(Color::LightGreen, Color::Black),
+            Rival => ColorCode::new(Color::Pink, Color::Black),
             Wall => ColorCode::new(Color::Yellow, Color::Red),
-            Appl => ColorCode::new(Color::Red, Color::Black),
+            Warp => ColorCode::new(Color::LightBlue, Color::Black),
+            Pal::Food(FoodKind::Apple) => ColorCode::new(Color::Red, Color::Black),
+            Pal::Food(FoodKind::Golden) => ColorCode::new(Color::Yellow, Color::Black),
+            Pal::Food(FoodKind::Poison) => ColorCode::new(Color::Magenta, Color::Black),
+            Pal::Food(FoodKind::Shrink) => ColorCode::new(Color::LightCyan, Color::Black),
+            Pal::Food(FoodKind::Speed) => ColorCode::new(Color::White, Color::Black),
+            Pal::Food(FoodKind::Power(_)) => ColorCode::new(Color::LightGreen, Color::Black),
             Text => ColorCode::new(Color::White, Color::Blue),
+            Highlight => ColorCode::new(Color::Blue, Color::LightGray),
             _ => ColorCode::new(Color::Black, Color::Black)
         }
     }
+
+    /// This colour's foreground on the HUD's background.
+    fn hud(self) -> ColorCode {
+        ColorCode::new(self.disp().foreground(), Color::Blue)
+    }
 }
 
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
@@ -602,3 +1545,860 @@ pub enum BodyTrail {
     Right2Down,
     Left2Down
 }
+
+impl BodyTrail {
+    /// The segment shape that connects the sides of a cell facing `a` and `b`.
+    fn joining(a: Dir, b: Dir) -> BodyTrail {
+        match (a, b) {
+            (Dir::Left, Dir::Right) | (Dir::Right, Dir::Left) => Hori,
+            (Dir::Up, Dir::Down) | (Dir::Down, Dir::Up) => Vert,
+            (Dir::Left, Dir::Up) | (Dir::Up, Dir::Left) => Right2Up,
+            (Dir::Right, Dir::Up) | (Dir::Up, Dir::Right) => Left2Up,
+            (Dir::Left, Dir::Down) | (Dir::Down, Dir::Left) => Right2Down,
+            (Dir::Right, Dir::Down) | (Dir::Down, Dir::Right) => Left2Down,
+            (Dir::Left, _) | (Dir::Right, _) => Hori,
+            (Dir::Up, _) | (Dir::Down, _) => Vert
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hud::NOTE_COL;
+
+    fn driver() -> SnakeDriver<Grid> {
+        let mut d = SnakeDriver::new(Grid::default());
+        d.draw_frame();
+        d
+    }
+
+    /// Ticks until the snake has made exactly one move, or through the death sequence
+    /// if it crashed, or through the count back in if the tick resumed a paused game.
+    fn step(d: &mut SnakeDriver<Grid>) {
+        for _ in 0..=d.countdown {
+            d.tick();
+        }
+        while matches!(d.status, Death | Resuming) {
+            d.tick();
+        }
+    }
+
+    fn steps(d: &mut SnakeDriver<Grid>, n: usize) {
+        for _ in 0..n {
+            step(d);
+        }
+    }
+
+    fn press(d: &mut SnakeDriver<Grid>, code: KeyCode) {
+        d.key(DecodedKey::RawKey(code));
+    }
+
+    /// Leaves `kind` on `cell` as the only food on the board.
+    fn put_food(d: &mut SnakeDriver<Grid>, cell: Cell, kind: FoodKind) {
+        d.food.clear();
+        d.food.add(Food::new(cell, kind));
+    }
+
+    #[test]
+    fn safe_add_wraps() {
+        assert_eq!(safe_add::<10>(7, 5), 2);
+        assert_eq!(add1::<BUFFER_WIDTH>(BUFFER_WIDTH - 1), 0);
+        assert_eq!(sub1::<BUFFER_HEIGHT>(0), BUFFER_HEIGHT - 1);
+        assert_eq!(Cell::new(0, 5).step(Dir::Left), Cell::new(BUFFER_WIDTH - 1, 5));
+        assert_eq!(Cell::new(3, BUFFER_HEIGHT - 1).step(Dir::Down), Cell::new(3, 0));
+    }
+
+    #[test]
+    fn moves_right_and_grows_at_start() {
+        let mut d = driver();
+        let start = d.body().head();
+        steps(&mut d, 3);
+        assert_eq!(d.body().head(), Cell::new(start.col() + 3, start.row()));
+        assert_eq!(d.body().len(), GameSettings::default().start_length as usize);
+        assert_eq!(d.status(), GameOn);
+        assert_eq!(d.renderer().char_at(start.col() + 3, start.row()), Sym::disp(Head));
+    }
+
+    #[test]
+    fn turns_and_ignores_reversal() {
+        let mut d = driver();
+        let start = d.body().head();
+        press(&mut d, KeyCode::ArrowUp);
+        step(&mut d);
+        assert_eq!(d.body().head(), Cell::new(start.col(), start.row() - 1));
+        press(&mut d, KeyCode::ArrowDown);
+        step(&mut d);
+        assert_eq!(d.body().head(), Cell::new(start.col(), start.row() - 2));
+        d.key(DecodedKey::Unicode('a'));
+        step(&mut d);
+        assert_eq!(d.body().head(), Cell::new(start.col() - 1, start.row() - 2));
+    }
+
+    #[test]
+    fn quick_presses_cannot_reverse_the_snake() {
+        let mut d = driver();
+        steps(&mut d, 3);
+        let start = d.body().head();
+        press(&mut d, KeyCode::ArrowUp);
+        press(&mut d, KeyCode::ArrowDown);
+        press(&mut d, KeyCode::ArrowLeft);
+        steps(&mut d, 2);
+        assert_eq!(d.status(), GameOn);
+        assert_eq!(d.body().head(), Cell::new(start.col() - 1, start.row() - 1));
+    }
+
+    #[test]
+    fn eating_an_apple_scores_and_grows() {
+        let mut d = driver();
+        steps(&mut d, 4);
+        let len = d.body().len();
+        let ahead = d.body().head().step(Dir::Right);
+        put_food(&mut d, ahead, FoodKind::Apple);
+        step(&mut d);
+        assert_eq!(d.score(), 1);
+        assert_ne!(d.food.apple(), Some(ahead));
+        let growth = d.rules.growth as usize;
+        steps(&mut d, growth);
+        assert_eq!(d.body().len(), len + growth);
+        step(&mut d);
+        assert_eq!(d.body().len(), len + growth);
+    }
+
+    #[test]
+    fn bonus_foods_score_shrink_and_speed_up() {
+        let mut d = driver();
+        d.players[0].growth = 3;
+        steps(&mut d, 6);
+        let ahead = d.body().head().step(Dir::Right);
+        put_food(&mut d, ahead, FoodKind::Golden);
+        d.redraw();
+        assert_eq!(d.renderer().char_at(ahead.col(), ahead.row()), '$');
+        step(&mut d);
+        assert_eq!(d.score(), food::GOLDEN_POINTS);
+
+        let len = d.body().len();
+        put_food(&mut d, ahead.step(Dir::Right), FoodKind::Shrink);
+        step(&mut d);
+        assert_eq!(d.body().len(), len + 1 - food::SHRINK_SEGMENTS);
+        assert!(!d.players[0].is_growing());
+
+        put_food(&mut d, ahead.step(Dir::Right).step(Dir::Right), FoodKind::Speed);
+        d.settings.speed = 1;
+        d.rules.speed = 1;
+        step(&mut d);
+        assert_eq!(d.countdown, d.rules.move_delay() / 2);
+        steps(&mut d, food::SPEED_MOVES);
+        assert_eq!(d.countdown, d.rules.move_delay());
+        assert_eq!(d.score(), food::GOLDEN_POINTS + 1);
+    }
+
+    #[test]
+    fn poison_is_fatal() {
+        let mut d = driver();
+        steps(&mut d, 2);
+        let head = d.body().head();
+        put_food(&mut d, head.step(Dir::Right), FoodKind::Poison);
+        step(&mut d);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.body().head(), head);
+    }
+
+    #[test]
+    fn ghosts_pass_through_their_own_body() {
+        let mut d = driver();
+        d.players[0].growth = 10;
+        steps(&mut d, 5);
+        d.players[0].effects.grant(Power::Ghost);
+        for code in [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::ArrowDown] {
+            press(&mut d, code);
+            step(&mut d);
+        }
+        assert_eq!(d.status(), GameOn);
+        d.update_hud();
+        assert!(d.renderer().reads(NOTE_COL, 0, "G27 "));
+        press(&mut d, KeyCode::ArrowRight);
+        steps(&mut d, 27);
+        assert_eq!(d.status(), GameOn);
+        assert!(!d.players[0].effects.has(Power::Ghost));
+        d.update_hud();
+        assert!(d.renderer().reads(NOTE_COL, 0, "    "));
+    }
+
+    #[test]
+    fn shields_take_one_knock() {
+        let mut d = driver();
+        d.players[0].effects.grant(Power::Shield);
+        press(&mut d, KeyCode::ArrowUp);
+        let top = d.rules.playfield_cell(0).row();
+        let rows = d.body().head().row() - top;
+        steps(&mut d, rows);
+        let head = d.body().head();
+        step(&mut d);
+        assert_eq!(d.status(), GameOn);
+        assert_eq!(d.body().head(), head);
+        assert!(!d.players[0].effects.has(Power::Shield));
+        press(&mut d, KeyCode::ArrowRight);
+        step(&mut d);
+        assert_eq!(d.body().head(), head.step(Dir::Right));
+    }
+
+    #[test]
+    fn magnets_pull_food_and_slow_mo_halves_the_pace() {
+        let mut d = driver();
+        steps(&mut d, 3);
+        d.players[0].effects.grant(Power::Magnet);
+        d.players[0].effects.grant(Power::Slow);
+        let head = d.body().head();
+        put_food(&mut d, Cell::new(head.col() + 1, head.row() - 3), FoodKind::Apple);
+        step(&mut d);
+        let pulled = Cell::new(head.col() + 1, head.row() - 2);
+        assert_eq!(d.food.apple(), Some(pulled));
+        assert_eq!(d.renderer().char_at(pulled.col(), pulled.row()), Sym::disp(Sym::Food(FoodKind::Apple)));
+        assert_eq!(d.renderer().char_at(pulled.col(), pulled.row() - 1), ' ');
+        assert_eq!(d.countdown, d.rules.move_delay() * 2 + 1);
+    }
+
+    #[test]
+    fn running_into_the_body_ends_the_game() {
+        let mut d = driver();
+        d.players[0].growth = 10;
+        steps(&mut d, 5);
+        for code in [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::ArrowDown] {
+            press(&mut d, code);
+            step(&mut d);
+        }
+        assert_eq!(d.status(), GameOver);
+        let head = d.body().head();
+        step(&mut d);
+        assert_eq!(d.body().head(), head);
+    }
+
+    #[test]
+    fn tail_is_not_an_obstacle_when_it_moves_away() {
+        let mut d = driver();
+        steps(&mut d, 3);
+        assert_eq!(d.body().len(), 4);
+        for code in [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::ArrowDown] {
+            press(&mut d, code);
+            step(&mut d);
+        }
+        assert_eq!(d.status(), GameOn);
+    }
+
+    #[test]
+    fn hitting_the_border_ends_the_game() {
+        let mut d = driver();
+        steps(&mut d, BUFFER_WIDTH);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.body().head().col(), BUFFER_WIDTH - 2);
+        assert_eq!(d.stats.cause, Some(Cause::Wall));
+    }
+
+    #[test]
+    fn crashes_play_out_before_the_results() {
+        let mut d = driver();
+        while d.status() == GameOn {
+            d.tick();
+        }
+        assert_eq!(d.status(), Death);
+        let head = d.body().head();
+        press(&mut d, KeyCode::Escape);
+        d.key(DecodedKey::Unicode('r'));
+        let mut ticks = 0;
+        while d.status() == Death {
+            d.tick();
+            ticks += 1;
+        }
+        assert!(ticks > 20);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.body().head(), head);
+        assert!(d.renderer().reads(35, 6, "GAME OVER"));
+        assert!(d.commands.iter().all(|q| q.iter().next().is_none()));
+    }
+
+    #[test]
+    fn game_over_panel_sums_up_the_run() {
+        let mut d = driver();
+        d.players[0].growth = 10;
+        steps(&mut d, 5);
+        for code in [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::ArrowDown] {
+            press(&mut d, code);
+            step(&mut d);
+        }
+        assert_eq!(d.status(), GameOver);
+        assert_eq!((d.stats.moves, d.stats.turns, d.stats.max_length), (7, 3, 8));
+        assert!(d.renderer().reads(35, 6, "GAME OVER"));
+        assert!(d.renderer().reads(25, 12, "Moves           7"));
+        assert!(d.renderer().reads(25, 14, "Time            0:"));
+        assert!(d.renderer().reads(25, 15, "Killed by       its own body"));
+        assert!(d.renderer().reads(24, 18, "R: retry  V: replay  Esc: title"));
+        choose(&mut d, KeyCode::Escape);
+        assert_eq!(d.status(), StartScreen);
+    }
+
+    #[test]
+    fn escape_pauses_and_resumes() {
+        let mut d = driver();
+        press(&mut d, KeyCode::Escape);
+        step(&mut d);
+        assert_eq!(d.status(), Paused);
+        let head = d.body().head();
+        steps(&mut d, 3);
+        assert_eq!(d.body().head(), head);
+        d.key(DecodedKey::Unicode('\u{1B}'));
+        d.tick();
+        assert_eq!(d.status(), Resuming);
+        while d.status() == Resuming {
+            d.tick();
+        }
+        assert_eq!(d.body().head(), head);
+        assert_eq!(d.status(), GameOn);
+    }
+
+    #[test]
+    fn the_pause_menu_covers_the_board_and_puts_it_back() {
+        let mut d = driver();
+        press(&mut d, KeyCode::Escape);
+        step(&mut d);
+        assert!(d.renderer().reads(37, 8, "PAUSED"));
+        assert!(d.renderer().reads(29, 10, "> Resume"));
+        for code in [KeyCode::ArrowUp, KeyCode::ArrowUp] {
+            press(&mut d, code);
+            d.tick();
+        }
+        assert!(d.renderer().reads(29, 13, "> Controls"));
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert!(d.renderer().reads(29, 10, "Steer: WASD or arrows"));
+        d.key(DecodedKey::Unicode('\u{1B}'));
+        d.tick();
+        assert_eq!(d.status(), Paused);
+        press(&mut d, KeyCode::ArrowDown);
+        d.tick();
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert_eq!(d.status(), StartScreen);
+
+        let mut d = driver();
+        press(&mut d, KeyCode::Escape);
+        step(&mut d);
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert!(d.renderer().reads(39, 12, "3"));
+        while d.status() == Resuming {
+            d.tick();
+        }
+        let mut fresh = d;
+        fresh.redraw();
+        assert_eq!(d.renderer(), fresh.renderer());
+    }
+
+    #[test]
+    fn quick_saves_come_back_in_their_slots() {
+        let mut d = driver();
+        steps(&mut d, 2);
+        press(&mut d, KeyCode::F5);
+        assert!(d.renderer().reads(NOTE_COL, 0, "Saved to slot 1"));
+        let saved = d.body().head();
+        steps(&mut d, 3);
+        press(&mut d, KeyCode::F6);
+        press(&mut d, KeyCode::F9);
+        assert!(d.renderer().reads(NOTE_COL, 0, "Slot 2 is empty"));
+        press(&mut d, KeyCode::F6);
+        press(&mut d, KeyCode::F6);
+        press(&mut d, KeyCode::F9);
+        assert_eq!((d.body().head(), d.status()), (saved, Resuming));
+        assert!(d.renderer().reads(NOTE_COL, 0, "Loaded slot 1"));
+        while d.status() == Resuming {
+            d.tick();
+        }
+        for _ in 0..FLASH_TICKS {
+            d.tick();
+        }
+        assert!(d.renderer().reads(NOTE_COL, 0, "    "));
+        assert!(!d.recording().is_complete());
+    }
+
+    #[test]
+    fn holding_backspace_winds_a_practice_game_back() {
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, GameSettings { practice: true, ..GameSettings::default() });
+        d.reset();
+        assert!(d.renderer().reads(NOTE_COL, 0, "PRACTICE  Bksp: rewind"));
+        steps(&mut d, 3);
+        let earlier = (d.body().head(), d.body().len());
+        steps(&mut d, 4);
+        for _ in 0..4 {
+            d.key(DecodedKey::Unicode('\u{8}'));
+            d.tick();
+        }
+        assert_eq!(d.status(), Rewinding);
+        assert_eq!((d.body().head(), d.body().len()), earlier);
+        assert!(d.renderer().reads(NOTE_COL, 0, "REWIND  let go to play"));
+        for _ in 0..RELEASE_TICKS {
+            d.tick();
+        }
+        assert_eq!(d.status(), Resuming);
+        while d.status() == Resuming {
+            d.tick();
+        }
+        step(&mut d);
+        assert_eq!(d.body().head(), earlier.0.step(Dir::Right));
+        assert!(!d.recording().is_complete());
+
+        let mut d = driver();
+        steps(&mut d, 3);
+        d.key(DecodedKey::Unicode('\u{8}'));
+        step(&mut d);
+        assert_eq!((d.status(), d.body().len()), (GameOn, 4));
+    }
+
+    #[test]
+    fn practice_scores_stay_off_the_table() {
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, GameSettings { practice: true, ..GameSettings::default() });
+        d.reset();
+        d.players[0].score = 7;
+        play_to_the_end(&mut d);
+        assert_eq!(d.status(), GameOver);
+        assert!(d.high_scores.table(WallMode::Solid).entries().is_empty());
+        d.key(DecodedKey::Unicode('\u{8}'));
+        d.tick();
+        assert_eq!(d.status(), GameOver);
+    }
+
+    #[test]
+    fn a_scored_run_cannot_be_loaded_back() {
+        let mut d = driver();
+        d.players[0].score = 7;
+        press(&mut d, KeyCode::F5);
+        play_to_the_end(&mut d);
+        for c in "abc\n".chars() {
+            d.key(DecodedKey::Unicode(c));
+        }
+        choose(&mut d, KeyCode::Escape);
+        press(&mut d, KeyCode::F9);
+        assert_eq!(d.status(), GameOver);
+        d.key(DecodedKey::Unicode('r'));
+        d.tick();
+        press(&mut d, KeyCode::F9);
+        assert!(d.renderer().reads(NOTE_COL, 0, "Slot 1 is empty"));
+        play_to_the_end(&mut d);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.high_scores.table(WallMode::Solid).entries(), [Entry { initials: *b"ABC", score: 7 }]);
+    }
+
+    #[test]
+    fn a_game_with_a_save_loaded_is_not_ranked() {
+        let mut d = driver();
+        d.players[0].score = 7;
+        press(&mut d, KeyCode::F5);
+        step(&mut d);
+        press(&mut d, KeyCode::F9);
+        let scores = d.high_scores;
+        play_to_the_end(&mut d);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.high_scores, scores);
+        d.tick();
+        assert!(d.renderer().reads(NOTE_COL, 0, "Loaded save: unranked"));
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_the_same_game() {
+        let play = |seed| {
+            let mut d = SnakeDriver::with_seed(Grid::default(), seed);
+            d.draw_frame();
+            steps(&mut d, 2);
+            press(&mut d, KeyCode::ArrowUp);
+            steps(&mut d, 3);
+            press(&mut d, KeyCode::ArrowRight);
+            steps(&mut d, 2);
+            d
+        };
+        let (a, b, c) = (play(99), play(99), play(100));
+        assert_eq!(a.food, b.food);
+        assert_eq!(a.renderer(), b.renderer());
+        assert_ne!(a.food, c.food);
+    }
+
+    /// Plays a short game that ends against the top border, with a pause in the middle.
+    fn play_to_the_end(d: &mut SnakeDriver<Grid>) {
+        steps(d, 3);
+        press(d, KeyCode::ArrowUp);
+        step(d);
+        press(d, KeyCode::Escape);
+        steps(d, 4);
+        press(d, KeyCode::Escape);
+        step(d);
+        press(d, KeyCode::ArrowRight);
+        steps(d, 2);
+        press(d, KeyCode::ArrowUp);
+        while d.status() == GameOn {
+            step(d);
+        }
+    }
+
+    #[test]
+    fn records_consumed_inputs() {
+        let mut d = driver();
+        play_to_the_end(&mut d);
+        let commands: Vec<Command> = d.recording().events().iter().map(|e| e.command).collect();
+        assert_eq!(commands, [Command::Turn(Dir::Up), Command::Back, Command::Back,
+            Command::Turn(Dir::Right), Command::Turn(Dir::Up)]);
+        assert_eq!(d.recording().seed(), d.seed());
+    }
+
+    #[test]
+    fn replay_reproduces_the_last_game() {
+        let mut d = driver();
+        play_to_the_end(&mut d);
+        let (body, food, grid) = (*d.body(), d.food, *d.renderer());
+
+        d.key(DecodedKey::Unicode('v'));
+        d.tick();
+        assert!(d.is_replaying());
+        assert_eq!(d.body().len(), 1);
+        d.key(DecodedKey::Unicode('2'));
+        while d.is_replaying() {
+            d.tick();
+        }
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(*d.body(), body);
+        assert_eq!(d.food, food);
+        assert!((1..BUFFER_HEIGHT).all(|row| (0..BUFFER_WIDTH).all(|col|
+            d.renderer().peek(col, row) == grid.peek(col, row))));
+    }
+
+    #[test]
+    fn step_replay_waits_for_space() {
+        let mut d = driver();
+        play_to_the_end(&mut d);
+        d.key(DecodedKey::Unicode('v'));
+        d.tick();
+        d.key(DecodedKey::Unicode('3'));
+        let head = d.body().head();
+        for _ in 0..10 {
+            d.tick();
+        }
+        assert_eq!(d.body().head(), head);
+        d.key(DecodedKey::Unicode(' '));
+        d.tick();
+        assert_eq!(d.body().head(), head.step(Dir::Right));
+        d.key(DecodedKey::RawKey(KeyCode::Escape));
+        assert!(!d.is_replaying());
+        assert_eq!(d.status(), GameOver);
+    }
+
+    #[test]
+    fn settings_shape_the_game() {
+        let settings = GameSettings { speed: settings::MAX_SPEED, growth: 1, start_length: 2, width: 30, height: 12, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, settings);
+        d.draw_frame();
+        assert_eq!(d.body().head(), settings.start());
+        assert_eq!(d.renderer().char_at(29, 5), '|');
+        assert_eq!(d.renderer().char_at(5, 12), '=');
+        d.tick();
+        d.tick();
+        assert_eq!(d.body().len(), 2);
+        while d.status() == GameOn {
+            d.tick();
+        }
+        assert_eq!(d.body().head().col(), 28);
+    }
+
+    #[test]
+    fn wrap_mode_comes_out_the_other_side() {
+        let settings = GameSettings { walls: WallMode::Wrap, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, settings);
+        let row = d.body().head().row();
+        steps(&mut d, BUFFER_WIDTH);
+        assert_eq!(d.status(), GameOn);
+        assert_eq!(d.body().head(), Cell::new(BUFFER_WIDTH / 4 + 2, row));
+    }
+
+    #[test]
+    fn torus_mode_has_no_border() {
+        let settings = GameSettings { walls: WallMode::Torus, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, settings);
+        d.draw_frame();
+        assert_eq!(d.renderer().char_at(0, 5), ' ');
+        assert_eq!(d.renderer().char_at(5, 1), ' ');
+        assert!(d.renderer().reads(2, 0, "SCORE:"));
+        press(&mut d, KeyCode::ArrowUp);
+        steps(&mut d, BUFFER_HEIGHT);
+        assert_eq!(d.status(), GameOn);
+        assert!(settings.in_playfield(d.body().head()));
+    }
+
+    #[test]
+    fn options_screen_edits_the_next_games_settings() {
+        let mut d = driver();
+        press(&mut d, KeyCode::Escape);
+        step(&mut d);
+        d.key(DecodedKey::Unicode('o'));
+        d.tick();
+        assert_eq!(d.status(), Options);
+        assert!(d.renderer().reads(20, 1, "OPTIONS"));
+        for code in [KeyCode::ArrowDown, KeyCode::ArrowRight, KeyCode::ArrowRight] {
+            press(&mut d, code);
+            d.tick();
+        }
+        assert_eq!(d.settings().growth, 5);
+        assert_eq!(d.take_unsaved().map(|r| r.settings.growth), Some(5));
+        assert_eq!(d.take_unsaved(), None);
+        press(&mut d, KeyCode::ArrowDown);
+        d.tick();
+        assert_eq!(d.take_unsaved(), None);
+        press(&mut d, KeyCode::Escape);
+        d.tick();
+        assert_eq!(d.status(), Paused);
+        assert!(d.renderer().reads(2, 0, "SCORE:"));
+        assert_eq!(d.rules.growth, 3);
+        d.key(DecodedKey::Unicode('r'));
+        d.tick();
+        assert_eq!(d.status(), GameOn);
+        assert_eq!(d.rules.growth, 5);
+    }
+
+    #[test]
+    fn level_walls_are_drawn_and_kill() {
+        let settings = GameSettings { level: 1, width: 30, height: 12, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, settings);
+        d.draw_frame();
+        assert_eq!((d.rules.width, d.rules.height), (80, 24));
+        let wall = d.level.walls(&d.rules).next().unwrap();
+        assert_eq!(d.renderer().char_at(wall.col(), wall.row()), '#');
+        let (start, dir) = d.level.spawn(&d.rules, 0);
+        assert_eq!((d.body().head(), d.players[0].dir), (start, dir));
+        let head = Cell::new(wall.col() - 1, wall.row());
+        d.players[0] = Player::new(head, Dir::Right, 0);
+        step(&mut d);
+        assert_eq!(d.status(), GameOver);
+        assert_eq!(d.body().head(), head);
+    }
+
+    #[test]
+    fn broken_level_falls_back_to_the_open_field() {
+        let mut d = driver();
+        d.levels[1] = Err(LevelError::NoSpawn);
+        d.set_settings(GameSettings { level: 1, ..GameSettings::default() });
+        press(&mut d, KeyCode::Escape);
+        step(&mut d);
+        d.key(DecodedKey::Unicode('r'));
+        d.tick();
+        assert!(d.level.is_open());
+        assert_eq!(d.body().head(), d.rules.start());
+        assert!(d.renderer().reads(NOTE_COL, 0, "no spawn point"));
+    }
+
+    fn choose(d: &mut SnakeDriver<Grid>, code: KeyCode) {
+        press(d, code);
+        d.tick();
+    }
+
+    #[test]
+    fn title_menu_starts_a_game() {
+        let mut d = driver();
+        d.open_title();
+        d.tick();
+        assert_eq!(d.status(), StartScreen);
+        assert!(d.renderer().reads(29, 4, "A  HASTY  SNAKE  CLONE"));
+        assert!(d.renderer().reads(34, 19, "Replay (none yet)"));
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert_eq!(d.status(), GameOn);
+        assert!(d.renderer().reads(2, 0, "SCORE:"));
+    }
+
+    #[test]
+    fn title_menu_replays_and_quits() {
+        let mut d = driver();
+        play_to_the_end(&mut d);
+        choose(&mut d, KeyCode::Escape);
+        assert_eq!(d.status(), StartScreen);
+        for _ in 0..5 {
+            choose(&mut d, KeyCode::ArrowDown);
+        }
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert!(d.is_replaying());
+        d.key(DecodedKey::Unicode('\u{1B}'));
+        choose(&mut d, KeyCode::Escape);
+        assert_eq!(d.status(), StartScreen);
+        choose(&mut d, KeyCode::ArrowDown);
+        d.key(DecodedKey::Unicode('\n'));
+        d.tick();
+        assert_eq!(d.status(), Halted);
+    }
+
+    #[test]
+    fn idle_title_plays_a_demo_until_a_key_is_pressed() {
+        let mut d = driver();
+        play_to_the_end(&mut d);
+        let recording = *d.recording();
+        d.open_title();
+        for _ in 0..ATTRACT_TICKS {
+            d.tick();
+        }
+        assert_eq!(d.status(), GameOn);
+        assert!(d.renderer().reads(NOTE_COL, 0, "DEMO"));
+        steps(&mut d, 400);
+        assert_eq!(d.status(), GameOn);
+        assert!(d.score() > 0);
+        d.key(DecodedKey::Unicode('x'));
+        assert_eq!(d.status(), StartScreen);
+        assert_eq!(*d.recording(), recording);
+    }
+
+    #[test]
+    fn qualifying_score_asks_for_initials() {
+        let mut d = driver();
+        d.players[0].score = 7;
+        play_to_the_end(&mut d);
+        assert_eq!(d.status(), EnterName);
+        for c in "rvo\u{8}x\n".chars() {
+            d.key(DecodedKey::Unicode(c));
+        }
+        assert_eq!(d.status(), HighScores);
+        assert!(d.renderer().reads(28, 6, "> 1   RVX     7"));
+        choose(&mut d, KeyCode::Escape);
+        assert_eq!(d.status(), GameOver);
+        assert!(d.renderer().reads(32, 7, "NEW HIGH SCORE!"));
+        d.tick();
+        assert!(d.renderer().reads(NOTE_COL, 0, "H: scores"));
+        d.key(DecodedKey::Unicode('h'));
+        d.tick();
+        assert_eq!(d.status(), HighScores);
+        choose(&mut d, KeyCode::ArrowRight);
+        assert!(d.renderer().reads(33, 7, "No scores yet."));
+    }
+
+    fn two_player_driver() -> SnakeDriver<Grid> {
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, GameSettings { players: 2, ..GameSettings::default() });
+        d.draw_frame();
+        d
+    }
+
+    #[test]
+    fn two_players_steer_and_score_separately() {
+        let mut d = two_player_driver();
+        let (one, two) = (d.players()[0].body.head(), d.players()[1].body.head());
+        assert_eq!(two, Cell::new(BUFFER_WIDTH - 1 - one.col(), one.row()));
+        assert!(d.renderer().reads(2, 0, "P1:") && d.renderer().reads(14, 0, "P2:"));
+        d.key(DecodedKey::Unicode('w'));
+        press(&mut d, KeyCode::ArrowDown);
+        put_food(&mut d, two.step(Dir::Down).step(Dir::Down), FoodKind::Apple);
+        steps(&mut d, 2);
+        assert_eq!(d.players()[0].body.head(), one.step(Dir::Up).step(Dir::Up));
+        assert_eq!(d.players()[1].body.head(), two.step(Dir::Down).step(Dir::Down));
+        assert_eq!((d.players()[0].score, d.players()[1].score), (0, 1));
+        assert_eq!(d.renderer().peek(two.col(), two.row() + 1).1, Pal::disp(Snake2));
+        d.tick();
+        assert!(d.renderer().reads(20, 0, "1"));
+
+        // Player 2 reaches the bottom border a move before player 1 reaches the top.
+        while d.status() == GameOn {
+            step(&mut d);
+        }
+        assert_eq!(d.winner(), Some(0));
+        assert!(d.renderer().reads(33, 10, "PLAYER 1 WINS!"));
+        assert!(d.renderer().reads(32, 13, "Rounds won: 1 - 0"));
+        assert!(d.recording().events().iter().any(|e| e.command == Command::Steer(1, Dir::Down)));
+        let players = d.players;
+        d.key(DecodedKey::Unicode('v'));
+        while d.is_replaying() {
+            d.tick();
+        }
+        assert_eq!(d.players, players);
+        assert_eq!(d.wins, [1, 0]);
+    }
+
+    #[test]
+    fn a_speed_apple_hurries_both_players() {
+        let mut d = two_player_driver();
+        d.rules.speed = 1;
+        let (one, two) = (d.players()[0].body.head(), d.players()[1].body.head());
+        put_food(&mut d, two.step(Dir::Left), FoodKind::Speed);
+        step(&mut d);
+        assert_eq!(d.players()[1].score, 1);
+        assert_eq!(d.countdown, d.rules.move_delay() / 2);
+        step(&mut d);
+        assert_eq!(d.players()[0].body.head(), one.step(Dir::Right).step(Dir::Right));
+        assert_eq!(d.players()[1].body.head(), two.step(Dir::Left).step(Dir::Left));
+    }
+
+    #[test]
+    fn head_on_crashes_draw_and_body_hits_lose() {
+        let mut d = two_player_driver();
+        let row = d.body().head().row();
+        d.players[0] = Player::new(Cell::new(30, row), Dir::Right, 0);
+        d.players[1] = Player::new(Cell::new(32, row), Dir::Left, 0);
+        step(&mut d);
+        assert_eq!((d.status(), d.winner()), (GameOver, None));
+        assert!(d.renderer().reads(37, 10, "DRAW!"));
+        assert_eq!(d.take_finished_game().map(|g| g.initials), Some([0; INITIALS]));
+
+        d.key(DecodedKey::Unicode('r'));
+        d.tick();
+        d.players[0] = Player::new(Cell::new(30, row), Dir::Down, 0);
+        d.players[1] = Player::new(Cell::new(30, row + 1), Dir::Right, 5);
+        step(&mut d);
+        assert_eq!((d.status(), d.winner()), (GameOver, Some(1)));
+        assert!(d.renderer().reads(32, 13, "Rounds won: 0 - 1"));
+
+        d.key(DecodedKey::Unicode('r'));
+        d.tick();
+        d.players[0] = Player::new(Cell::new(30, row), Dir::Right, 0);
+        d.players[1] = Player::new(Cell::new(32, row), Dir::Left, 0);
+        d.players[0].effects.grant(Power::Shield);
+        step(&mut d);
+        assert_eq!(d.status(), GameOn);
+        assert_eq!((d.players()[0].body.head(), d.players()[1].body.head()), (Cell::new(30, row), Cell::new(31, row)));
+        assert!(!d.players[0].effects.has(Power::Shield));
+    }
+
+    #[test]
+    fn opponents_chase_apples_and_replay_the_same_way() {
+        let settings = GameSettings { opponents: 2, skill: Skill::Cautious, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, settings);
+        d.draw_frame();
+        assert_eq!(d.players().len(), 3);
+        step(&mut d);
+        let rival = d.players()[2].body.head();
+        assert_eq!(d.renderer().peek(rival.col(), rival.row()), (Sym::disp(Head), Pal::disp(Rival)));
+        play_to_the_end(&mut d);
+        assert!(d.players()[1..].iter().any(|p| p.score > 0));
+        assert!(d.recording().events().iter().all(|e| !matches!(e.command, Command::Steer(..))));
+        let players = d.players;
+        d.key(DecodedKey::Unicode('v'));
+        while d.is_replaying() {
+            d.tick();
+        }
+        assert_eq!(d.players, players);
+    }
+
+    #[test]
+    fn crashed_opponents_leave_the_board() {
+        let mut d = SnakeDriver::with_settings(Grid::default(), 5, GameSettings { opponents: 1, ..GameSettings::default() });
+        d.draw_frame();
+        // Player 1's growing tail shuts the opponent into the top-left corner.
+        d.players[0] = Player::new(Cell::new(2, 2), Dir::Down, 5);
+        d.players[0].body.push_head(Cell::new(2, 3));
+        d.players[1] = Player::new(Cell::new(1, 2), Dir::Up, 0);
+        d.redraw();
+        step(&mut d);
+        assert_eq!(d.status(), GameOn);
+        assert!(!d.players()[1].alive);
+        assert_eq!(d.renderer().char_at(1, 2), ' ');
+        assert!(d.is_vacant(Cell::new(1, 2)));
+        steps(&mut d, 2);
+        assert_eq!(d.body().head(), Cell::new(2, 6));
+    }
+
+    #[test]
+    fn frame_is_drawn_off_screen() {
+        let d = driver();
+        assert!(d.renderer().reads(2, 0, "SCORE:"));
+        assert_eq!(d.renderer().char_at(0, 5), '|');
+        assert_eq!(d.renderer().char_at(5, BUFFER_HEIGHT - 1), '=');
+    }
+}
@@ -4,7 +4,7 @@
 use crossbeam::atomic::AtomicCell;
 use pc_keyboard::DecodedKey;
 use pluggable_interrupt_os::{vga_buffer::clear_screen, HandlerTable};
-use a_hasty_snake_clone::SnakeDriver;
+use a_hasty_snake_clone::{ata::Ata, persist, rng, SnakeDriver, SpscQueue, Status, Storage, Vga};
 
 
 #[no_mangle]
@@ -17,26 +17,40 @@ pub extern "C" fn _start() -> ! {
         .start()
 }
 
-static LAST_KEY: AtomicCell<Option<DecodedKey>> = AtomicCell::new(None);
+static KEYS: SpscQueue<DecodedKey, 32> = SpscQueue::new();
 static TICKED: AtomicCell<bool> = AtomicCell::new(false);
 
 fn cpu_loop() -> ! {
-    let mut kernel = SnakeDriver::default();
+    let mut kernel = SnakeDriver::with_seed(Vga, rng::boot_entropy());
+    kernel.restore_record(&persist::load().unwrap_or_default());
+    let mut storage = Storage::new(Ata::primary_slave().ok());
+    storage.load_into(&mut kernel);
+    kernel.open_title();
     loop {
-        if let Ok(_) = TICKED.compare_exchange(true, false) {
+        if TICKED.compare_exchange(true, false).is_ok() {
             kernel.tick();
         }
         
-        if let Ok(k) = LAST_KEY.fetch_update(|k| if k.is_some() {Some(None)} else {None}) {
-            if let Some(k) = k {
-                kernel.key(k);
+        while let Some(k) = KEYS.pop() {
+            kernel.key(k);
+        }
+
+        if let Some(record) = kernel.take_unsaved() {
+            persist::save(&record);
+        }
+        storage.service(&mut kernel);
+
+        if kernel.status() == Status::Halted {
+            x86_64::instructions::interrupts::disable();
+            loop {
+                x86_64::instructions::hlt();
             }
         }
     }
 }
 
 fn key(key: DecodedKey) {
-    LAST_KEY.store(Some(key));
+    KEYS.push(key);
 }
 
 fn tick() {
@@ -45,5 +59,4 @@ fn tick() {
 
 fn startup() {
     clear_screen();
-    SnakeDriver::draw_frame();
 }
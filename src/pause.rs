@@ -0,0 +1,173 @@
+//! The menu that comes up over the board while a game is paused, and the count back
+//! into play once it is taken down.
+
+use crate::input::KeyLayout;
+use crate::render::Renderer;
+use crate::snake::Dir;
+use crate::ui::{draw_str, fill, Align, Dialog, MenuList, Rect};
+use crate::Pal;
+
+/// Timer ticks each number of the count stays up: about a second.
+const TICKS_PER_NUMBER: usize = 18;
+const COUNT_FROM: usize = 3;
+const MENU: Rect = Rect::centered(28, 9);
+const COUNT: Rect = Rect::centered(7, 3);
+
+/// The entries of the pause menu, top to bottom.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Options,
+    /// Lists the keys.
+    Controls,
+    /// Gives the game up and goes back to the title screen.
+    Quit
+}
+
+const ITEMS: [PauseItem; 5] = [
+    PauseItem::Resume, PauseItem::Restart, PauseItem::Options, PauseItem::Controls, PauseItem::Quit
+];
+
+impl PauseItem {
+    fn label(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Restart => "Restart",
+            PauseItem::Options => "Options",
+            PauseItem::Controls => "Controls",
+            PauseItem::Quit => "Quit to Title"
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PauseMenu {
+    list: MenuList,
+    /// Set while the list of keys is up in place of the menu.
+    controls: bool,
+    /// The menu or the count, and the board under it, while either is up.
+    dialog: Option<Dialog>,
+    /// Ticks left before play goes on.
+    count: usize
+}
+
+impl PauseMenu {
+    /// Puts the menu up over the board, keeping the entry that was last highlighted.
+    pub fn open<R: Renderer>(&mut self, r: &mut R) {
+        self.controls = false;
+        self.dialog = Some(Dialog::open(MENU, "PAUSED", r));
+        self.draw_menu(r);
+    }
+
+    /// Takes down whatever is up and puts the board back as it was.
+    pub fn close<R: Renderer>(&mut self, r: &mut R) {
+        if let Some(dialog) = self.dialog.take() {
+            dialog.close(r);
+        }
+    }
+
+    pub fn selected(&self) -> PauseItem {
+        ITEMS[self.list.selected()]
+    }
+
+    pub fn showing_controls(&self) -> bool {
+        self.controls
+    }
+
+    /// Up and Down move through the menu.
+    pub fn handle<R: Renderer>(&mut self, dir: Dir, r: &mut R) {
+        match dir {
+            Dir::Up => self.list.step(false, ITEMS.len()),
+            Dir::Down => self.list.step(true, ITEMS.len()),
+            _ => return
+        }
+        self.draw_menu(r);
+    }
+
+    /// Swaps the menu for the keys of a game with `players` players.
+    pub fn show_controls<R: Renderer>(&mut self, keys: KeyLayout, players: u8, r: &mut R) {
+        self.controls = true;
+        self.blank(r);
+        let text = Pal::disp(Pal::Text);
+        let (left, top) = (MENU.left + 3, MENU.top + 2);
+        let (label, rest) = if players > 1 { ("P1: ", "  P2: arrows") } else { ("Steer: ", " or arrows") };
+        let end = r.plot_str(label, left, top, text);
+        let end = r.plot_str(keys.name(), end, top, text);
+        r.plot_str(rest, end, top, text);
+        r.plot_str("Esc: pause, resume", left, top + 1, text);
+        r.plot_str("R: restart", left, top + 2, text);
+        r.plot_str("O: options", left, top + 3, text);
+        draw_str(r, "Esc: back", MENU.middle(), MENU.bottom() - 1, Align::Center, text);
+    }
+
+    /// Goes back from the keys to the menu.
+    pub fn hide_controls<R: Renderer>(&mut self, r: &mut R) {
+        self.controls = false;
+        self.draw_menu(r);
+    }
+
+    /// Takes the menu down and starts counting back into the game.
+    pub fn start_count<R: Renderer>(&mut self, r: &mut R) {
+        self.close(r);
+        self.dialog = Some(Dialog::open(COUNT, "", r));
+        self.count = COUNT_FROM * TICKS_PER_NUMBER;
+        self.draw_count(r);
+    }
+
+    /// Counts off a tick. Returns false, with the board uncovered, once the count is over.
+    pub fn count_down<R: Renderer>(&mut self, r: &mut R) -> bool {
+        self.count = self.count.saturating_sub(1);
+        if self.count == 0 {
+            self.close(r);
+            return false;
+        }
+        self.draw_count(r);
+        true
+    }
+
+    /// Clears the inside of the menu box.
+    fn blank<R: Renderer>(&self, r: &mut R) {
+        for row in MENU.top + 1..MENU.bottom() {
+            fill(r, MENU.left + 1, MENU.right(), row, Pal::disp(Pal::Text));
+        }
+    }
+
+    fn draw_menu<R: Renderer>(&self, r: &mut R) {
+        self.blank(r);
+        self.list.draw(&ITEMS.map(PauseItem::label), MENU.left + 3, MENU.top + 2, MENU.width - 6, r);
+    }
+
+    fn draw_count<R: Renderer>(&self, r: &mut R) {
+        let number = self.count.div_ceil(TICKS_PER_NUMBER);
+        r.plot((b'0' + number as u8) as char, COUNT.middle(), COUNT.top + 1, Pal::disp(Pal::Text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Grid;
+
+    #[test]
+    fn the_count_runs_three_two_one_then_uncovers_the_board() {
+        let mut grid = Grid::default();
+        grid.plot_str("board", COUNT.left, COUNT.top + 1, Pal::disp(Pal::Snake));
+        let before = grid;
+        let mut menu = PauseMenu::default();
+        menu.open(&mut grid);
+        menu.handle(Dir::Down, &mut grid);
+        assert_eq!(menu.selected(), PauseItem::Restart);
+        menu.start_count(&mut grid);
+        let mut seen = vec![grid.char_at(COUNT.middle(), COUNT.top + 1)];
+        let mut ticks = 1;
+        while menu.count_down(&mut grid) {
+            seen.push(grid.char_at(COUNT.middle(), COUNT.top + 1));
+            ticks += 1;
+        }
+        seen.dedup();
+        assert_eq!(seen, ['3', '2', '1']);
+        assert_eq!(ticks, COUNT_FROM * TICKS_PER_NUMBER);
+        assert_eq!(grid, before);
+    }
+}
@@ -0,0 +1,202 @@
+//! Keeps the settings and the best scores across reboots in the CMOS RAM bytes the
+//! firmware leaves spare.
+//!
+//! The record is a magic byte, a version byte, the settings, the top `SAVED_SCORES`
+//! of every mode's table and a CRC-8 over all of it. Anything that doesn't check out
+//! is reported and the caller starts from defaults.
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::cmos;
+use crate::scores::{Entry, HighScores, INITIALS};
+use crate::settings::{GameSettings, WallMode};
+
+/// Entries kept per mode. The rest of the session's table is lost on reboot.
+pub const SAVED_SCORES: usize = 4;
+
+const MAGIC: u8 = 0x5A;
+const VERSION: u8 = 1;
+const SETTINGS_LEN: usize = GameSettings::ENCODED_LEN;
+const ENTRY_LEN: usize = 4;
+const SCORES_START: usize = 2 + SETTINGS_LEN;
+pub const RECORD_LEN: usize = SCORES_START + WallMode::ALL.len() * SAVED_SCORES * ENTRY_LEN + 1;
+
+/// CMOS bytes nothing else uses. QEMU keeps memory sizes and the CPU count in
+/// 0x5B..0x60, and everything below 0x40 belongs to the clock and the BIOS.
+const SPARE: [Range<u8>; 2] = [0x40..0x5B, 0x60..0x80];
+
+const _: () = assert!(RECORD_LEN <= (0x5B - 0x40) + (0x80 - 0x60));
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LoadError {
+    /// Nothing has been saved yet, or something else owns the bytes.
+    Blank,
+    /// Saved by a build with a different layout.
+    WrongVersion(u8),
+    BadChecksum,
+    /// The checksum matched but a field is out of range.
+    BadValue
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Blank => write!(f, "nothing saved"),
+            LoadError::WrongVersion(v) => write!(f, "saved by version {}, expected {}", v, VERSION),
+            LoadError::BadChecksum => write!(f, "checksum mismatch"),
+            LoadError::BadValue => write!(f, "saved value out of range")
+        }
+    }
+}
+
+/// What survives a reboot.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Record {
+    pub settings: GameSettings,
+    pub scores: HighScores
+}
+
+impl Record {
+    pub fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0] = MAGIC;
+        bytes[1] = VERSION;
+        bytes[2..SCORES_START].copy_from_slice(&self.settings.encode());
+        let slots = bytes[SCORES_START..RECORD_LEN - 1].chunks_exact_mut(ENTRY_LEN);
+        let entries = WallMode::ALL.iter().flat_map(|&mode| {
+            let saved = self.scores.table(mode).entries();
+            (0..SAVED_SCORES).map(move |i| saved.get(i).copied())
+        });
+        for (slot, entry) in slots.zip(entries) {
+            slot.copy_from_slice(&pack(entry));
+        }
+        bytes[RECORD_LEN - 1] = crc8(&bytes[..RECORD_LEN - 1]);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; RECORD_LEN]) -> Result<Self, LoadError> {
+        if bytes[0] != MAGIC {
+            return Err(LoadError::Blank);
+        }
+        if bytes[1] != VERSION {
+            return Err(LoadError::WrongVersion(bytes[1]));
+        }
+        if crc8(&bytes[..RECORD_LEN - 1]) != bytes[RECORD_LEN - 1] {
+            return Err(LoadError::BadChecksum);
+        }
+        let mut settings = [0; SETTINGS_LEN];
+        settings.copy_from_slice(&bytes[2..SCORES_START]);
+        let settings = GameSettings::decode(settings).ok_or(LoadError::BadValue)?;
+        let mut scores = HighScores::default();
+        let slots = bytes[SCORES_START..RECORD_LEN - 1].chunks_exact(ENTRY_LEN);
+        let modes = WallMode::ALL.iter().flat_map(|&mode| [mode; SAVED_SCORES]);
+        for (slot, mode) in slots.zip(modes) {
+            if let Some(entry) = unpack(slot)? {
+                scores.table_mut(mode).insert(entry);
+            }
+        }
+        Ok(Self { settings, scores })
+    }
+}
+
+/// Five bits per initial (0 for none, 1 to 26 for A to Z) above a 16-bit score. An
+/// empty slot has a zero score.
+fn pack(entry: Option<Entry>) -> [u8; ENTRY_LEN] {
+    let Some(entry) = entry else { return [0; ENTRY_LEN] };
+    let letters = entry.initials.iter()
+        .fold(0u32, |acc, &c| acc << 5 | if c == 0 { 0 } else { (c - b'A' + 1) as u32 });
+    (letters << 16 | entry.score as u32).to_le_bytes()
+}
+
+fn unpack(slot: &[u8]) -> Result<Option<Entry>, LoadError> {
+    let packed = u32::from_le_bytes([slot[0], slot[1], slot[2], slot[3]]);
+    let score = packed as u16;
+    if score == 0 {
+        return Ok(None);
+    }
+    let mut initials = [0; INITIALS];
+    for (i, c) in initials.iter_mut().enumerate() {
+        *c = match (packed >> (16 + 5 * (INITIALS - 1 - i))) & 0x1F {
+            0 => 0,
+            n @ 1..=26 => b'A' + n as u8 - 1,
+            _ => return Err(LoadError::BadValue)
+        };
+    }
+    Ok(Some(Entry { initials, score }))
+}
+
+/// CRC-8 with the polynomial x^8 + x^2 + x + 1.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| if crc & 0x80 != 0 { crc << 1 ^ 0x07 } else { crc << 1 })
+    })
+}
+
+fn registers() -> impl Iterator<Item = u8> {
+    SPARE.into_iter().flatten()
+}
+
+/// Reads the saved record back out of CMOS.
+///
+/// Only meaningful in the kernel, like everything in `cmos`.
+pub fn load() -> Result<Record, LoadError> {
+    let mut bytes = [0; RECORD_LEN];
+    for (byte, register) in bytes.iter_mut().zip(registers()) {
+        *byte = cmos::read(register);
+    }
+    Record::decode(&bytes)
+}
+
+pub fn save(record: &Record) {
+    for (byte, register) in record.encode().into_iter().zip(registers()) {
+        cmos::write(register, byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::KeyLayout;
+    use crate::scores::NameEntry;
+
+    fn sample() -> Record {
+        let mut record = Record::default();
+        record.settings.walls = WallMode::Torus;
+        record.settings.keys = KeyLayout::Ijkl;
+        record.settings.level = 2;
+        for (i, initials) in ["ann", "bo", "cyz", "dee", "eve"].iter().enumerate() {
+            let mut name = NameEntry::default();
+            initials.chars().for_each(|c| name.push(c));
+            record.scores.table_mut(WallMode::Wrap).insert(name.entry(10 * (i as u16 + 1)));
+        }
+        record
+    }
+
+    #[test]
+    fn round_trips_settings_and_top_scores() {
+        let record = sample();
+        let decoded = Record::decode(&record.encode()).unwrap();
+        assert_eq!(decoded.settings, record.settings);
+        let saved = decoded.scores.table(WallMode::Wrap).entries();
+        assert_eq!(saved, &record.scores.table(WallMode::Wrap).entries()[..SAVED_SCORES]);
+        assert_eq!(saved[3].initials(), "BO");
+        assert!(decoded.scores.table(WallMode::Solid).entries().is_empty());
+    }
+
+    #[test]
+    fn rejects_blank_and_damaged_storage() {
+        assert_eq!(Record::decode(&[0; RECORD_LEN]), Err(LoadError::Blank));
+        assert_eq!(Record::decode(&[0xFF; RECORD_LEN]), Err(LoadError::Blank));
+        let mut bytes = sample().encode();
+        bytes[1] = 9;
+        assert_eq!(Record::decode(&bytes), Err(LoadError::WrongVersion(9)));
+        let mut bytes = sample().encode();
+        bytes[20] ^= 0x10;
+        assert_eq!(Record::decode(&bytes), Err(LoadError::BadChecksum));
+        let mut bytes = sample().encode();
+        bytes[2] = 0;
+        bytes[RECORD_LEN - 1] = crc8(&bytes[..RECORD_LEN - 1]);
+        assert_eq!(Record::decode(&bytes), Err(LoadError::BadValue));
+    }
+}
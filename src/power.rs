@@ -0,0 +1,117 @@
+//! Power-ups: foods that give the snake that eats them an effect for a number of moves.
+
+use crate::snapshot::{Reader, Writer};
+
+/// How far, in rows plus columns, a magnet reaches for food.
+pub const MAGNET_RANGE: usize = 6;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Power {
+    /// The head passes through the snake's own body.
+    Ghost,
+    /// The game runs at half speed.
+    Slow,
+    /// The next crash into a wall, a body or another snake's head is shrugged off, and
+    /// the snake waits a move instead.
+    Shield,
+    /// Food within `MAGNET_RANGE` creeps one cell per move toward the head.
+    Magnet
+}
+
+impl Power {
+    pub const ALL: [Power; 4] = [Power::Ghost, Power::Slow, Power::Shield, Power::Magnet];
+
+    /// Position in `ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Moves the effect lasts.
+    pub fn duration(self) -> u16 {
+        match self {
+            Power::Ghost => 30,
+            Power::Slow => 40,
+            Power::Shield => 60,
+            Power::Magnet => 50
+        }
+    }
+
+    /// The letter that stands for it on the HUD.
+    pub(crate) fn letter(self) -> char {
+        match self {
+            Power::Ghost => 'G',
+            Power::Slow => 'S',
+            Power::Shield => 'H',
+            Power::Magnet => 'M'
+        }
+    }
+}
+
+/// The power-ups a snake has running, as moves left on each.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Effects {
+    left: [u16; Power::ALL.len()]
+}
+
+impl Effects {
+    /// Starts `power`, or tops it back up to its full duration.
+    pub fn grant(&mut self, power: Power) {
+        self.left[power.index()] = power.duration();
+    }
+
+    pub fn has(&self, power: Power) -> bool {
+        self.left[power.index()] != 0
+    }
+
+    /// Moves left on `power`, or 0 if it isn't running.
+    pub fn left(&self, power: Power) -> u16 {
+        self.left[power.index()]
+    }
+
+    /// Spends the shield, if there is one up.
+    pub fn use_shield(&mut self) -> bool {
+        let up = self.has(Power::Shield);
+        self.left[Power::Shield.index()] = 0;
+        up
+    }
+
+    /// Counts one move off everything running.
+    pub fn wear(&mut self) {
+        for left in self.left.iter_mut() {
+            *left = left.saturating_sub(1);
+        }
+    }
+
+    pub(crate) fn write_to(&self, w: &mut Writer) {
+        self.left.iter().for_each(|&left| w.u16(left));
+    }
+
+    pub(crate) fn read_from(r: &mut Reader) -> Option<Self> {
+        let mut effects = Self::default();
+        for (left, power) in effects.left.iter_mut().zip(Power::ALL) {
+            *left = r.u16()?.min(power.duration());
+        }
+        Some(effects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_wear_off_and_shields_break_once() {
+        let mut effects = Effects::default();
+        effects.grant(Power::Ghost);
+        effects.grant(Power::Shield);
+        for _ in 1..Power::Ghost.duration() {
+            effects.wear();
+        }
+        assert_eq!(effects.left(Power::Ghost), 1);
+        effects.wear();
+        assert!(!effects.has(Power::Ghost));
+        assert!(effects.use_shield());
+        assert!(!effects.use_shield());
+        assert!(!effects.has(Power::Magnet));
+    }
+}
@@ -0,0 +1,189 @@
+use core::fmt;
+
+#[cfg(feature = "vga")]
+use pluggable_interrupt_os::vga_buffer;
+
+pub const BUFFER_WIDTH: usize = 80;
+pub const BUFFER_HEIGHT: usize = 25;
+
+/// The 16 text-mode colours, numbered the way the VGA attribute byte expects.
+#[repr(u8)]
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum Color {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Magenta = 5,
+    Brown = 6,
+    LightGray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    Pink = 13,
+    Yellow = 14,
+    White = 15
+}
+
+impl Color {
+    const ALL: [Color; 16] = [
+        Color::Black, Color::Blue, Color::Green, Color::Cyan,
+        Color::Red, Color::Magenta, Color::Brown, Color::LightGray,
+        Color::DarkGray, Color::LightBlue, Color::LightGreen, Color::LightCyan,
+        Color::LightRed, Color::Pink, Color::Yellow, Color::White
+    ];
+
+    fn from_nibble(n: u8) -> Color {
+        Color::ALL[(n & 0xF) as usize]
+    }
+}
+
+/// A foreground/background pair packed like a VGA attribute byte.
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct ColorCode(u8);
+
+impl ColorCode {
+    pub const fn new(foreground: Color, background: Color) -> ColorCode {
+        ColorCode((background as u8) << 4 | (foreground as u8))
+    }
+
+    pub fn foreground(&self) -> Color {
+        Color::from_nibble(self.0)
+    }
+
+    pub fn background(&self) -> Color {
+        Color::from_nibble(self.0 >> 4)
+    }
+}
+
+/// Something `SnakeDriver` can draw on. Coordinates are screen cells, with the same
+/// layout and size as the VGA text buffer.
+pub trait Renderer {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode);
+
+    fn peek(&self, col: usize, row: usize) -> (char, ColorCode);
+
+    fn clear_screen(&mut self) {
+        let black = ColorCode::new(Color::Black, Color::Black);
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                self.plot(' ', col, row, black);
+            }
+        }
+    }
+
+    /// Draws `s` starting at `col`, cutting it off at the right edge.
+    /// Returns the column after the last character drawn.
+    fn plot_str(&mut self, s: &str, col: usize, row: usize, color: ColorCode) -> usize {
+        let mut end = col;
+        for (c, chr) in (col..BUFFER_WIDTH).zip(s.chars()) {
+            self.plot(chr, c, row, color);
+            end = c + 1;
+        }
+        end
+    }
+
+    /// Draws `num` in decimal starting at `col`. Returns the column after the last digit.
+    fn plot_num(&mut self, num: isize, col: usize, row: usize, color: ColorCode) -> usize {
+        let mut digits = [0u8; 20];
+        let mut n = num.unsigned_abs();
+        let mut len = 0;
+        loop {
+            digits[len] = b'0' + (n % 10) as u8;
+            len += 1;
+            n /= 10;
+            if n == 0 { break; }
+        }
+        let mut c = col;
+        if num < 0 && c < BUFFER_WIDTH {
+            self.plot('-', c, row, color);
+            c += 1;
+        }
+        for d in digits[..len].iter().rev() {
+            if c >= BUFFER_WIDTH { break; }
+            self.plot(*d as char, c, row, color);
+            c += 1;
+        }
+        c
+    }
+}
+
+/// Draws straight into the VGA text buffer. This is what the kernel uses.
+#[cfg(feature = "vga")]
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Default)]
+pub struct Vga;
+
+#[cfg(feature = "vga")]
+impl Vga {
+    fn code(color: ColorCode) -> vga_buffer::ColorCode {
+        vga_buffer::ColorCode::new(
+            vga_buffer::Color::from(color.foreground() as u8),
+            vga_buffer::Color::from(color.background() as u8)
+        )
+    }
+}
+
+#[cfg(feature = "vga")]
+impl Renderer for Vga {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode) {
+        vga_buffer::plot(c, col, row, Self::code(color));
+    }
+
+    fn peek(&self, col: usize, row: usize) -> (char, ColorCode) {
+        let (c, code) = vga_buffer::peek(col, row);
+        (c, ColorCode::new(Color::from_nibble(code.foreground() as u8), Color::from_nibble(code.background() as u8)))
+    }
+}
+
+/// An off-screen copy of the text buffer, for frontends that are not the VGA console
+/// and for checking exactly what a frame looks like.
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Grid {
+    cells: [[(u8, ColorCode); BUFFER_WIDTH]; BUFFER_HEIGHT]
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self { cells: [[(b' ', ColorCode::new(Color::Black, Color::Black)); BUFFER_WIDTH]; BUFFER_HEIGHT] }
+    }
+}
+
+impl Grid {
+    pub fn char_at(&self, col: usize, row: usize) -> char {
+        self.cells[row][col].0 as char
+    }
+
+    pub fn color_at(&self, col: usize, row: usize) -> ColorCode {
+        self.cells[row][col].1
+    }
+
+    /// Whether the text starting at (`col`, `row`) reads `s`.
+    pub fn reads(&self, col: usize, row: usize, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| col + i < BUFFER_WIDTH && self.char_at(col + i, row) == c)
+    }
+}
+
+impl Renderer for Grid {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode) {
+        self.cells[row][col] = (c as u8, color);
+    }
+
+    fn peek(&self, col: usize, row: usize) -> (char, ColorCode) {
+        (self.char_at(col, row), self.color_at(col, row))
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.cells.iter() {
+            for (c, _) in row.iter() {
+                write!(f, "{}", *c as char)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::input::Command;
+use crate::settings::GameSettings;
+
+/// Room for this many consumed commands per game. Longer games still play, but can't be replayed.
+pub const MAX_EVENTS: usize = 2048;
+
+/// Timer ticks run per real tick when replaying at `ReplaySpeed::Fast`.
+pub const FAST_TICKS: usize = 4;
+
+/// One command `SnakeDriver::handle_input` consumed, and the game tick it was consumed on.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Event {
+    pub tick: u32,
+    pub command: Command
+}
+
+/// Everything needed to play a game again: the seed and settings it started from and
+/// the commands it consumed, in order.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Recording {
+    seed: u64,
+    settings: GameSettings,
+    events: [Event; MAX_EVENTS],
+    len: usize,
+    overflowed: bool
+}
+
+impl Recording {
+    pub fn new(seed: u64, settings: GameSettings) -> Self {
+        Self { seed, settings, events: [Event { tick: 0, command: Command::Back }; MAX_EVENTS], len: 0, overflowed: false }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn settings(&self) -> GameSettings {
+        self.settings
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events[..self.len]
+    }
+
+    /// False once a command had to be dropped, since playing it back would diverge.
+    pub fn is_complete(&self) -> bool {
+        !self.overflowed
+    }
+
+    /// Marks a recording that is known to have lost commands, e.g. one read back from
+    /// disk that had overflowed when it was saved.
+    pub fn mark_incomplete(&mut self) {
+        self.overflowed = true;
+    }
+
+    pub fn push(&mut self, tick: u32, command: Command) {
+        if self.len < MAX_EVENTS {
+            self.events[self.len] = Event { tick, command };
+            self.len += 1;
+        } else {
+            self.overflowed = true;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ReplaySpeed {
+    Normal,
+    Fast,
+    /// Only moves when asked to, one snake move at a time.
+    Step
+}
+
+/// Where a replay in progress has got to.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Playback {
+    pub speed: ReplaySpeed,
+    pub steps_requested: usize,
+    cursor: usize
+}
+
+impl Playback {
+    pub fn new(speed: ReplaySpeed) -> Self {
+        Self { speed, steps_requested: 0, cursor: 0 }
+    }
+
+    /// The recorded command for game tick `tick`, if there was one.
+    pub fn next_command(&mut self, recording: &Recording, tick: u32) -> Option<Command> {
+        match recording.events().get(self.cursor) {
+            Some(event) if event.tick == tick => {
+                self.cursor += 1;
+                Some(event.command)
+            }
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! The last stretch of a practice game, one snapshot per move, for Backspace to wind
+//! back through.
+//!
+//! Snapshots are kept end to end in a fixed byte ring, each with its length on both
+//! sides so the newest can be taken off one end and the oldest dropped off the other.
+//! A short snake's snapshot is a fraction of the size of a long one's, so how far back
+//! the ring reaches depends on how much is on the board.
+
+use crate::snapshot::{Snapshot, MAX_SNAPSHOT_LEN};
+
+/// Room for a few seconds of moves even with every snake at a good length.
+pub const REWIND_BYTES: usize = 16 * 1024;
+/// The length written before and after each snapshot.
+const LEN_BYTES: usize = 2;
+
+const _: () = assert!(MAX_SNAPSHOT_LEN + 2 * LEN_BYTES <= REWIND_BYTES);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rewind {
+    ring: [u8; REWIND_BYTES],
+    /// Where the oldest snapshot starts.
+    start: usize,
+    /// Bytes in use from `start` on.
+    used: usize
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self { ring: [0; REWIND_BYTES], start: 0, used: 0 }
+    }
+}
+
+impl Rewind {
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.used = 0;
+    }
+
+    /// Keeps `snapshot` as the newest, dropping the oldest ones to make room.
+    pub fn push(&mut self, snapshot: &Snapshot) {
+        let bytes = snapshot.as_bytes();
+        let len = (bytes.len() as u16).to_le_bytes();
+        while REWIND_BYTES - self.used < bytes.len() + 2 * LEN_BYTES {
+            let oldest = u16::from_le_bytes([self.at(0), self.at(1)]) as usize;
+            self.start = (self.start + oldest + 2 * LEN_BYTES) % REWIND_BYTES;
+            self.used -= oldest + 2 * LEN_BYTES;
+        }
+        for &b in len.iter().chain(bytes).chain(&len) {
+            self.ring[(self.start + self.used) % REWIND_BYTES] = b;
+            self.used += 1;
+        }
+    }
+
+    /// Takes off the newest snapshot.
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        if self.is_empty() {
+            return None;
+        }
+        let len = u16::from_le_bytes([self.at(self.used - 2), self.at(self.used - 1)]) as usize;
+        self.used -= len + 2 * LEN_BYTES;
+        let mut bytes = [0; MAX_SNAPSHOT_LEN];
+        for (i, b) in bytes[..len].iter_mut().enumerate() {
+            *b = self.at(self.used + LEN_BYTES + i);
+        }
+        Snapshot::from_bytes(&bytes[..len]).ok()
+    }
+
+    /// The byte `i` on from the start of the oldest snapshot.
+    fn at(&self, i: usize) -> u8 {
+        self.ring[(self.start + i) % REWIND_BYTES]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Grid;
+    use crate::SnakeDriver;
+
+    #[test]
+    fn the_newest_come_back_first_and_the_oldest_make_room() {
+        let mut d = SnakeDriver::new(Grid::default());
+        let mut rewind = Rewind::default();
+        let mut taken = Vec::new();
+        for ticks in 0..400 {
+            d.ticks = ticks;
+            let snapshot = d.snapshot().unwrap();
+            rewind.push(&snapshot);
+            taken.push(snapshot);
+        }
+        let kept = core::iter::from_fn(|| rewind.pop()).collect::<Vec<_>>();
+        assert!(kept.len() < taken.len());
+        assert!(kept.len() * (taken[0].as_bytes().len() + 2 * LEN_BYTES) > REWIND_BYTES - MAX_SNAPSHOT_LEN);
+        assert!(kept.iter().eq(taken.iter().rev().take(kept.len())));
+        assert!(rewind.is_empty());
+    }
+}
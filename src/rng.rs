@@ -0,0 +1,106 @@
+use core::ops::Range;
+
+use crate::cmos;
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+/// A PCG32 generator (O'Neill, "PCG: A Family of Simple Fast Space-Efficient
+/// Statistically Good Algorithms for Random Number Generation").
+///
+/// Every random choice the game makes comes from here, so a game is fully determined
+/// by the seed it started from and the inputs it was given.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Picks up a sequence where `state` left off.
+    pub fn from_state(state: u64) -> Self {
+        Self { state }
+    }
+
+    /// Where the sequence has got to, for saving.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+
+    /// A uniformly distributed value in `range`, which must not be empty.
+    pub fn gen_range(&mut self, range: Range<usize>) -> usize {
+        assert!(range.start < range.end, "gen_range called with an empty range");
+        let span = (range.end - range.start) as u32;
+        // Throw away the top sliver of outputs that would make low values more likely.
+        let limit = u32::MAX - u32::MAX % span;
+        loop {
+            let x = self.next_u32();
+            if x < limit {
+                return range.start + (x % span) as usize;
+            }
+        }
+    }
+}
+
+/// A seed that differs from boot to boot, mixed from the CPU's timestamp counter and
+/// the CMOS real-time clock. Kernel only.
+pub fn boot_entropy() -> u64 {
+    let tsc = unsafe { core::arch::x86_64::_rdtsc() };
+    let rtc = cmos::rtc_time() as u64;
+    splitmix64(tsc ^ rtc.rotate_left(32))
+}
+
+/// Spreads the few bits that actually vary in `x` across the whole word.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let xs: [u32; 8] = core::array::from_fn(|_| a.next_u32());
+        let ys: [u32; 8] = core::array::from_fn(|_| b.next_u32());
+        let zs: [u32; 8] = core::array::from_fn(|_| c.next_u32());
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+
+    #[test]
+    fn gen_range_covers_the_range_evenly() {
+        let mut rng = Rng::new(7);
+        let mut counts = [0usize; 6];
+        for _ in 0..6000 {
+            let x = rng.gen_range(10..16);
+            assert!((10..16).contains(&x));
+            counts[x - 10] += 1;
+        }
+        assert!(counts.iter().all(|&n| (850..1150).contains(&n)), "{:?}", counts);
+    }
+}
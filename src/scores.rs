@@ -0,0 +1,188 @@
+use crate::render::Renderer;
+use crate::settings::WallMode;
+use crate::Pal;
+
+/// Entries kept per game mode.
+pub const TABLE_SIZE: usize = 10;
+pub const INITIALS: usize = 3;
+
+/// One line of a high score table. Scores are points from food eaten, capped at
+/// `u16::MAX`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Entry {
+    pub initials: [u8; INITIALS],
+    pub score: u16
+}
+
+impl Entry {
+    pub fn initials(&self) -> &str {
+        let len = self.initials.iter().position(|&b| b == 0).unwrap_or(INITIALS);
+        core::str::from_utf8(&self.initials[..len]).unwrap_or("???")
+    }
+}
+
+/// The best `TABLE_SIZE` scores for one game mode, best first.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ScoreTable {
+    entries: [Entry; TABLE_SIZE],
+    len: usize
+}
+
+impl Default for ScoreTable {
+    fn default() -> Self {
+        Self { entries: [Entry { initials: [0; INITIALS], score: 0 }; TABLE_SIZE], len: 0 }
+    }
+}
+
+impl ScoreTable {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..self.len]
+    }
+
+    pub fn contains(&self, entry: Entry) -> bool {
+        self.entries().contains(&entry)
+    }
+
+    /// Whether `score` would make it onto the table. Nothing qualifies with zero.
+    pub fn qualifies(&self, score: u16) -> bool {
+        score > 0 && (self.len < TABLE_SIZE || score > self.entries[TABLE_SIZE - 1].score)
+    }
+
+    /// Adds `entry` below any equal scores, dropping the last one if the table is full.
+    /// Returns where it landed, or `None` if it didn't qualify.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self.entries().iter().position(|e| e.score < entry.score).unwrap_or(self.len);
+        self.len = (self.len + 1).min(TABLE_SIZE);
+        self.entries.copy_within(rank..self.len - 1, rank + 1);
+        self.entries[rank] = entry;
+        Some(rank)
+    }
+}
+
+/// A score table for every game mode, kept for the whole session.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct HighScores {
+    tables: [ScoreTable; WallMode::ALL.len()]
+}
+
+impl HighScores {
+    pub fn table(&self, mode: WallMode) -> &ScoreTable {
+        &self.tables[mode.index()]
+    }
+
+    pub fn table_mut(&mut self, mode: WallMode) -> &mut ScoreTable {
+        &mut self.tables[mode.index()]
+    }
+
+    /// Draws the table for `mode`, marking row `highlight` if given.
+    pub fn draw<R: Renderer>(&self, mode: WallMode, highlight: Option<usize>, r: &mut R) {
+        r.clear_screen();
+        let text = Pal::disp(Pal::Text);
+        let end = r.plot_str("HIGH SCORES: < ", 26, 3, text);
+        let end = r.plot_str(mode.name(), end, 3, text);
+        r.plot_str(" walls >", end, 3, text);
+        let entries = self.table(mode).entries();
+        if entries.is_empty() {
+            r.plot_str("No scores yet.", 33, 7, text);
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            let row = 6 + i;
+            if highlight == Some(i) {
+                r.plot('>', 28, row, text);
+            }
+            r.plot_num(i as isize + 1, 30, row, text);
+            r.plot_str(entry.initials(), 34, row, text);
+            r.plot_num(entry.score as isize, 42, row, text);
+        }
+        r.plot_str("Left/Right: mode  Esc: back", 26, 7 + TABLE_SIZE, text);
+    }
+}
+
+/// Initials being typed in after a qualifying game.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct NameEntry {
+    initials: [u8; INITIALS],
+    len: usize
+}
+
+impl NameEntry {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Takes a letter, upper-cased. Anything else, or a fourth letter, is ignored.
+    pub fn push(&mut self, c: char) {
+        if c.is_ascii_alphabetic() && self.len < INITIALS {
+            self.initials[self.len] = c.to_ascii_uppercase() as u8;
+            self.len += 1;
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if self.len > 0 {
+            self.len -= 1;
+            self.initials[self.len] = 0;
+        }
+    }
+
+    pub fn entry(&self, score: u16) -> Entry {
+        Entry { initials: self.initials, score }
+    }
+
+    pub fn draw<R: Renderer>(&self, score: u16, r: &mut R) {
+        r.clear_screen();
+        let text = Pal::disp(Pal::Text);
+        r.plot_str("NEW HIGH SCORE!", 32, 5, text);
+        let end = r.plot_str("Score: ", 34, 7, text);
+        r.plot_num(score as isize, end, 7, text);
+        r.plot_str("Your initials:", 28, 10, text);
+        for i in 0..INITIALS {
+            let c = if i < self.len { self.initials[i] as char } else { '_' };
+            r.plot(c, 44 + 2 * i, 10, text);
+        }
+        r.plot_str("Enter: done  Backspace: erase  Esc: skip", 20, 13, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &str, score: u16) -> Entry {
+        let mut name = NameEntry::default();
+        initials.chars().for_each(|c| name.push(c));
+        name.entry(score)
+    }
+
+    #[test]
+    fn table_keeps_the_best_in_order() {
+        let mut table = ScoreTable::default();
+        assert_eq!(table.insert(entry("abc", 5)), Some(0));
+        assert_eq!(table.insert(entry("def", 9)), Some(0));
+        assert_eq!(table.insert(entry("ghi", 5)), Some(2));
+        assert_eq!(table.insert(entry("zzz", 0)), None);
+        for score in 10..20 {
+            table.insert(entry("x", score));
+        }
+        assert_eq!(table.entries().len(), TABLE_SIZE);
+        assert_eq!(table.entries()[0].score, 19);
+        assert_eq!(table.entries()[TABLE_SIZE - 1].score, 10);
+        assert!(!table.qualifies(10));
+        assert_eq!(table.insert(entry("def", 12)), Some(8));
+        assert_eq!(table.entries()[TABLE_SIZE - 1].score, 11);
+    }
+
+    #[test]
+    fn name_entry_takes_three_letters() {
+        let mut name = NameEntry::default();
+        for c in "a1bcd".chars() {
+            name.push(c);
+        }
+        assert_eq!(name.entry(1).initials(), "ABC");
+        name.pop();
+        assert_eq!(name.entry(1).initials(), "AB");
+    }
+}
@@ -0,0 +1,408 @@
+use crate::ai::Skill;
+use crate::input::KeyLayout;
+use crate::level;
+use crate::render::{Renderer, BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::snake::{Cell, Dir};
+use crate::Pal;
+
+/// Fastest selectable speed. At this speed the snake moves on every timer tick.
+pub const MAX_SPEED: u8 = 5;
+
+pub const MAX_GROWTH: u8 = 20;
+pub const MAX_START_LENGTH: u8 = 20;
+
+/// Snakes that can share the board, one per player.
+pub const MAX_PLAYERS: u8 = 2;
+/// Computer snakes that can join the players.
+pub const MAX_OPPONENTS: u8 = 2;
+/// Every snake a game can have: the players first, then their opponents.
+pub const MAX_SNAKES: usize = (MAX_PLAYERS + MAX_OPPONENTS) as usize;
+
+pub const MIN_WIDTH: u8 = 20;
+pub const MIN_HEIGHT: u8 = 10;
+/// The board sits under the HUD row, so it can use every row but one.
+pub const MAX_WIDTH: u8 = BUFFER_WIDTH as u8;
+pub const MAX_HEIGHT: u8 = BUFFER_HEIGHT as u8 - 1;
+
+/// What happens when the snake reaches the edge of the playfield.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WallMode {
+    /// The border kills.
+    Solid,
+    /// The border is still drawn, but the snake passes under it and comes back in on
+    /// the opposite side.
+    Wrap,
+    /// No border at all: the playfield takes the whole board and wraps at its edges.
+    Torus
+}
+
+impl WallMode {
+    pub const ALL: [WallMode; 3] = [WallMode::Solid, WallMode::Wrap, WallMode::Torus];
+
+    /// Position in `ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn has_border(self) -> bool {
+        self != WallMode::Torus
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            WallMode::Solid => "solid",
+            WallMode::Wrap => "wrap",
+            WallMode::Torus => "torus"
+        }
+    }
+
+    pub(crate) fn cycle(self, up: bool) -> Self {
+        match (self, up) {
+            (WallMode::Solid, true) | (WallMode::Torus, false) => WallMode::Wrap,
+            (WallMode::Wrap, true) | (WallMode::Solid, false) => WallMode::Torus,
+            (WallMode::Torus, true) | (WallMode::Wrap, false) => WallMode::Solid
+        }
+    }
+}
+
+/// Everything about a game that can be tuned without rebuilding the kernel.
+///
+/// The board is drawn from the top-left corner just below the HUD row, border
+/// included, so a `width` x `height` board leaves `width - 2` x `height - 2` cells to
+/// play in, or all of it on a `WallMode::Torus` board.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameSettings {
+    /// 1 to `MAX_SPEED`.
+    pub speed: u8,
+    /// Segments gained per apple.
+    pub growth: u8,
+    pub start_length: u8,
+    pub walls: WallMode,
+    pub width: u8,
+    pub height: u8,
+    /// Index into the level list; 0 is the open field. Any other level sets the board
+    /// size itself, overriding `width` and `height`.
+    pub level: u8,
+    pub keys: KeyLayout,
+    /// 1 to `MAX_PLAYERS`. Player 1 steers with `keys`, player 2 with the arrows.
+    pub players: u8,
+    /// Computer snakes on the board as well, up to `MAX_OPPONENTS`.
+    pub opponents: u8,
+    pub skill: Skill,
+    /// Lets Backspace wind the game back, and keeps its scores off the table.
+    pub practice: bool
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            speed: MAX_SPEED - 1,
+            growth: 3,
+            start_length: 4,
+            walls: WallMode::Solid,
+            width: MAX_WIDTH,
+            height: MAX_HEIGHT,
+            level: 0,
+            keys: KeyLayout::Wasd,
+            players: 1,
+            opponents: 0,
+            skill: Skill::Greedy,
+            practice: false
+        }
+    }
+}
+
+impl GameSettings {
+    /// Bytes taken by `encode`.
+    pub const ENCODED_LEN: usize = 8;
+
+    /// A compact form for saving, one byte per field except the player count and the
+    /// practice flag, which share the walls byte, and the opponents, which share the
+    /// keys byte, so the record still fits in CMOS. One player on their own encodes as
+    /// they did before there was anyone else.
+    pub fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        [
+            self.speed, self.growth, self.start_length,
+            (self.practice as u8) << 7 | self.players.saturating_sub(1) << 4 | self.walls.index() as u8,
+            self.width, self.height, self.level,
+            (self.skill.index() as u8) << 6 | self.opponents << 4 | self.keys.index() as u8
+        ]
+    }
+
+    /// Reverses `encode`, or `None` if any field is out of range.
+    pub fn decode(b: [u8; Self::ENCODED_LEN]) -> Option<Self> {
+        let settings = GameSettings {
+            speed: b[0],
+            growth: b[1],
+            start_length: b[2],
+            walls: *WallMode::ALL.get((b[3] & 0x0F) as usize)?,
+            width: b[4],
+            height: b[5],
+            level: b[6],
+            keys: *KeyLayout::ALL.get((b[7] & 0x0F) as usize)?,
+            players: (b[3] >> 4 & 0x07) + 1,
+            opponents: b[7] >> 4 & 0x03,
+            skill: *Skill::ALL.get((b[7] >> 6) as usize)?,
+            practice: b[3] & 0x80 != 0
+        };
+        if settings.is_valid() { Some(settings) } else { None }
+    }
+
+    /// Whether every field is in the range the options screen allows.
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_SPEED).contains(&self.speed)
+            && self.growth <= MAX_GROWTH
+            && (1..=MAX_START_LENGTH).contains(&self.start_length)
+            && (MIN_WIDTH..=MAX_WIDTH).contains(&self.width)
+            && (MIN_HEIGHT..=MAX_HEIGHT).contains(&self.height)
+            && (self.level as usize) < level::COUNT
+            && (1..=MAX_PLAYERS).contains(&self.players)
+            && self.opponents <= MAX_OPPONENTS
+    }
+
+    /// Players and opponents together.
+    pub fn snakes(&self) -> usize {
+        (self.players + self.opponents) as usize
+    }
+
+    /// Timer ticks to wait between moves.
+    pub fn move_delay(&self) -> usize {
+        (MAX_SPEED - self.speed) as usize
+    }
+
+    /// How far the playfield is inset from the board's edges.
+    fn inset(&self) -> usize {
+        if self.walls.has_border() { 1 } else { 0 }
+    }
+
+    fn left(&self) -> usize {
+        self.inset()
+    }
+
+    fn right(&self) -> usize {
+        self.width as usize - 1 - self.inset()
+    }
+
+    /// Row 0 is the HUD, so the board starts on row 1.
+    fn top(&self) -> usize {
+        1 + self.inset()
+    }
+
+    fn bottom(&self) -> usize {
+        self.height as usize - self.inset()
+    }
+
+    /// Width and height of the area the snake can move in.
+    pub fn playfield_size(&self) -> (usize, usize) {
+        (self.right() - self.left() + 1, self.bottom() - self.top() + 1)
+    }
+
+    /// The `i`th playfield cell in reading order, wrapping past the last one.
+    pub fn playfield_cell(&self, i: usize) -> Cell {
+        let (w, h) = self.playfield_size();
+        let i = i % (w * h);
+        Cell::new(self.left() + i % w, self.top() + i / w)
+    }
+
+    pub fn in_playfield(&self, cell: Cell) -> bool {
+        (self.left()..=self.right()).contains(&cell.col()) && (self.top()..=self.bottom()).contains(&cell.row())
+    }
+
+    /// Where the snake's head starts out.
+    pub fn start(&self) -> Cell {
+        let (w, h) = self.playfield_size();
+        Cell::new(self.left() + w / 4, self.top() + h / 2)
+    }
+
+    /// The cell the head reaches by moving from `cell` along `dir`, or `None` if that
+    /// means hitting the border. Wrapping stays inside the playfield, so the snake never
+    /// lands on the HUD or border rows.
+    pub fn next_cell(&self, cell: Cell, dir: Dir) -> Option<Cell> {
+        let next = cell.step(dir);
+        if self.in_playfield(next) {
+            return Some(next);
+        }
+        match self.walls {
+            WallMode::Solid => None,
+            WallMode::Wrap | WallMode::Torus => Some(match dir {
+                Dir::Up => Cell::new(cell.col(), self.bottom()),
+                Dir::Down => Cell::new(cell.col(), self.top()),
+                Dir::Left => Cell::new(self.right(), cell.row()),
+                Dir::Right => Cell::new(self.left(), cell.row())
+            })
+        }
+    }
+}
+
+/// The rows of the options screen.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Field {
+    Speed,
+    Growth,
+    StartLength,
+    Walls,
+    Width,
+    Height,
+    Level,
+    Keys,
+    Skill,
+    Practice
+}
+
+const FIELDS: [Field; 10] = [
+    Field::Speed, Field::Growth, Field::StartLength, Field::Walls,
+    Field::Width, Field::Height, Field::Level, Field::Keys, Field::Skill, Field::Practice
+];
+
+impl Field {
+    fn label(self) -> &'static str {
+        match self {
+            Field::Speed => "Speed",
+            Field::Growth => "Growth per apple",
+            Field::StartLength => "Starting length",
+            Field::Walls => "Walls",
+            Field::Width => "Board width",
+            Field::Height => "Board height",
+            Field::Level => "Level",
+            Field::Keys => "Steering keys",
+            Field::Skill => "Computer snakes",
+            Field::Practice => "Practice (rewind)"
+        }
+    }
+
+    /// Steps the field up or down by one, staying within its limits.
+    fn adjust(self, settings: &mut GameSettings, up: bool) {
+        fn nudge(value: &mut u8, up: bool, min: u8, max: u8) {
+            *value = if up { (*value + 1).min(max) } else { value.saturating_sub(1).max(min) };
+        }
+        match self {
+            Field::Speed => nudge(&mut settings.speed, up, 1, MAX_SPEED),
+            Field::Growth => nudge(&mut settings.growth, up, 0, MAX_GROWTH),
+            Field::StartLength => nudge(&mut settings.start_length, up, 1, MAX_START_LENGTH),
+            Field::Walls => settings.walls = settings.walls.cycle(up),
+            Field::Width => nudge(&mut settings.width, up, MIN_WIDTH, MAX_WIDTH),
+            Field::Height => nudge(&mut settings.height, up, MIN_HEIGHT, MAX_HEIGHT),
+            Field::Level => nudge(&mut settings.level, up, 0, level::COUNT as u8 - 1),
+            Field::Keys => settings.keys = settings.keys.cycle(up),
+            Field::Skill => settings.skill = settings.skill.cycle(up),
+            Field::Practice => settings.practice = !settings.practice
+        }
+    }
+
+    fn plot_value<R: Renderer>(self, settings: &GameSettings, r: &mut R, col: usize, row: usize) {
+        let color = Pal::disp(Pal::Text);
+        match self {
+            Field::Speed => r.plot_num(settings.speed as isize, col, row, color),
+            Field::Growth => r.plot_num(settings.growth as isize, col, row, color),
+            Field::StartLength => r.plot_num(settings.start_length as isize, col, row, color),
+            Field::Walls => r.plot_str(settings.walls.name(), col, row, color),
+            Field::Width => r.plot_num(settings.width as isize, col, row, color),
+            Field::Height => r.plot_num(settings.height as isize, col, row, color),
+            Field::Level => {
+                r.plot_num(settings.level as isize, col, row, color);
+                r.plot_str(level::name(settings.level as usize), col + 9, row, color)
+            }
+            Field::Keys => r.plot_str(settings.keys.name(), col, row, color),
+            Field::Skill => r.plot_str(settings.skill.name(), col, row, color),
+            Field::Practice => r.plot_str(if settings.practice { "on" } else { "off" }, col, row, color)
+        };
+    }
+}
+
+const MENU_COL: usize = 20;
+const MENU_ROW: usize = 3;
+const VALUE_COL: usize = MENU_COL + 22;
+
+/// The options screen: Up/Down picks a setting and Left/Right changes it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct OptionsMenu {
+    selected: usize
+}
+
+impl OptionsMenu {
+    pub fn handle(&mut self, dir: Dir, settings: &mut GameSettings) {
+        match dir {
+            Dir::Up => self.selected = (self.selected + FIELDS.len() - 1) % FIELDS.len(),
+            Dir::Down => self.selected = (self.selected + 1) % FIELDS.len(),
+            Dir::Left => FIELDS[self.selected].adjust(settings, false),
+            Dir::Right => FIELDS[self.selected].adjust(settings, true)
+        }
+    }
+
+    pub fn draw<R: Renderer>(&self, settings: &GameSettings, r: &mut R) {
+        r.clear_screen();
+        let text = Pal::disp(Pal::Text);
+        r.plot_str("OPTIONS", MENU_COL, MENU_ROW - 2, text);
+        for (i, field) in FIELDS.iter().enumerate() {
+            let row = MENU_ROW + 2 * i;
+            r.plot(if i == self.selected { '>' } else { ' ' }, MENU_COL - 2, row, text);
+            r.plot_str(field.label(), MENU_COL, row, text);
+            r.plot_str("<       >", VALUE_COL - 2, row, text);
+            field.plot_value(settings, r, VALUE_COL, row);
+        }
+        r.plot_str("Changes apply from the next game. Esc: back", MENU_COL, MENU_ROW + 2 * FIELDS.len() + 1, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_walls(walls: WallMode) -> GameSettings {
+        GameSettings { walls, width: 30, height: 12, ..GameSettings::default() }
+    }
+
+    #[test]
+    fn solid_walls_stop_the_snake() {
+        let s = with_walls(WallMode::Solid);
+        assert_eq!(s.next_cell(Cell::new(28, 5), Dir::Right), None);
+        assert_eq!(s.next_cell(Cell::new(4, 2), Dir::Up), None);
+        assert_eq!(s.next_cell(Cell::new(4, 5), Dir::Up), Some(Cell::new(4, 4)));
+    }
+
+    #[test]
+    fn wrap_skips_the_border_and_hud() {
+        let s = with_walls(WallMode::Wrap);
+        assert_eq!(s.next_cell(Cell::new(28, 5), Dir::Right), Some(Cell::new(1, 5)));
+        assert_eq!(s.next_cell(Cell::new(1, 5), Dir::Left), Some(Cell::new(28, 5)));
+        assert_eq!(s.next_cell(Cell::new(4, 2), Dir::Up), Some(Cell::new(4, 11)));
+        assert_eq!(s.next_cell(Cell::new(4, 11), Dir::Down), Some(Cell::new(4, 2)));
+    }
+
+    #[test]
+    fn torus_uses_the_whole_board() {
+        let s = with_walls(WallMode::Torus);
+        assert_eq!(s.playfield_size(), (30, 12));
+        assert_eq!(s.next_cell(Cell::new(29, 5), Dir::Right), Some(Cell::new(0, 5)));
+        assert_eq!(s.next_cell(Cell::new(0, 5), Dir::Left), Some(Cell::new(29, 5)));
+        assert_eq!(s.next_cell(Cell::new(4, 1), Dir::Up), Some(Cell::new(4, 12)));
+        assert_eq!(s.next_cell(Cell::new(4, 12), Dir::Down), Some(Cell::new(4, 1)));
+    }
+
+    #[test]
+    fn player_and_opponent_counts_fit_in_spare_bits() {
+        let one = GameSettings { walls: WallMode::Torus, ..GameSettings::default() };
+        let two = GameSettings { players: 2, ..one };
+        assert_eq!(one.encode()[3], 2);
+        assert_eq!(GameSettings::decode(two.encode()), Some(two));
+        let rivals = GameSettings { opponents: 2, skill: Skill::Survivor, keys: KeyLayout::Ijkl, ..two };
+        assert_eq!(rivals.encode()[7], 0xA2);
+        assert_eq!(GameSettings::decode(rivals.encode()), Some(rivals));
+        let mut bytes = two.encode();
+        bytes[3] = 0x22;
+        assert_eq!(GameSettings::decode(bytes), None);
+        let practice = GameSettings { practice: true, ..two };
+        assert_eq!(practice.encode()[3], 0x92);
+        assert_eq!(GameSettings::decode(practice.encode()), Some(practice));
+    }
+
+    #[test]
+    fn wall_mode_cycles_both_ways() {
+        let mut mode = WallMode::Solid;
+        for _ in 0..3 {
+            mode = mode.cycle(true);
+        }
+        assert_eq!(mode, WallMode::Solid);
+        assert_eq!(mode.cycle(false), WallMode::Torus);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::power::Effects;
+use crate::render::{BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::settings::GameSettings;
+use crate::snapshot::{Reader, Writer};
+
+use crate::{add1, sub1};
+
+/// Enough room for a snake that fills the whole screen.
+pub const MAX_LENGTH: usize = BUFFER_WIDTH * BUFFER_HEIGHT;
+
+/// One screen position. Stored as bytes so a full-length body stays small.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Cell {
+    col: u8,
+    row: u8
+}
+
+impl Cell {
+    pub fn new(col: usize, row: usize) -> Self {
+        Self { col: col as u8, row: row as u8 }
+    }
+
+    pub fn col(&self) -> usize {
+        self.col as usize
+    }
+
+    pub fn row(&self) -> usize {
+        self.row as usize
+    }
+
+    /// The neighbouring cell in direction `dir`, wrapping around the screen edges.
+    pub fn step(self, dir: Dir) -> Self {
+        match dir {
+            Dir::Up => Self::new(self.col(), sub1::<BUFFER_HEIGHT>(self.row())),
+            Dir::Down => Self::new(self.col(), add1::<BUFFER_HEIGHT>(self.row())),
+            Dir::Left => Self::new(sub1::<BUFFER_WIDTH>(self.col()), self.row()),
+            Dir::Right => Self::new(add1::<BUFFER_WIDTH>(self.col()), self.row()),
+        }
+    }
+
+    /// The direction that leads from `self` to the adjacent cell `other`, if they touch.
+    pub fn dir_to(self, other: Cell) -> Option<Dir> {
+        Dir::ALL.into_iter().find(|d| self.step(*d) == other)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Dir {
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+impl Dir {
+    pub const ALL: [Dir; 4] = [Dir::Up, Dir::Down, Dir::Left, Dir::Right];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Dir::Up => Dir::Down,
+            Dir::Down => Dir::Up,
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+        }
+    }
+}
+
+/// The cells a snake occupies, kept in a fixed-capacity ring buffer.
+/// Index 0 is the head and `len() - 1` is the tail.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SnakeBody {
+    cells: [Cell; MAX_LENGTH],
+    head: usize,
+    len: usize
+}
+
+impl SnakeBody {
+    pub fn new(start: Cell) -> Self {
+        let mut cells = [Cell::default(); MAX_LENGTH];
+        cells[0] = start;
+        Self { cells, head: 0, len: 1 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == MAX_LENGTH
+    }
+
+    /// The `i`th segment counting back from the head.
+    pub fn get(&self, i: usize) -> Option<Cell> {
+        if i < self.len {
+            Some(self.cells[(self.head + i) % MAX_LENGTH])
+        } else {
+            None
+        }
+    }
+
+    pub fn head(&self) -> Cell {
+        self.cells[self.head]
+    }
+
+    pub fn tail(&self) -> Cell {
+        self.cells[(self.head + self.len - 1) % MAX_LENGTH]
+    }
+
+    /// Segments from head to tail.
+    pub fn iter(&self) -> impl Iterator<Item = Cell> + '_ {
+        (0..self.len).map(move |i| self.cells[(self.head + i) % MAX_LENGTH])
+    }
+
+    pub fn contains(&self, cell: Cell) -> bool {
+        self.iter().any(|c| c == cell)
+    }
+
+    /// Whether moving the head onto `cell` would run into the body. The tail only
+    /// counts when it is not about to move out of the way, i.e. while `growing`.
+    pub fn blocks(&self, cell: Cell, growing: bool) -> bool {
+        let solid = if growing { self.len } else { self.len - 1 };
+        self.iter().take(solid).any(|c| c == cell)
+    }
+
+    /// Adds a new head. Does nothing if the buffer is already full.
+    pub fn push_head(&mut self, cell: Cell) {
+        if !self.is_full() {
+            self.head = (self.head + MAX_LENGTH - 1) % MAX_LENGTH;
+            self.cells[self.head] = cell;
+            self.len += 1;
+        }
+    }
+
+    /// Adds a segment behind the tail. Does nothing if the buffer is already full.
+    pub fn push_tail(&mut self, cell: Cell) {
+        if !self.is_full() {
+            self.cells[(self.head + self.len) % MAX_LENGTH] = cell;
+            self.len += 1;
+        }
+    }
+
+    /// Removes and returns the tail, always leaving at least the head behind.
+    pub fn pop_tail(&mut self) -> Option<Cell> {
+        if self.len > 1 {
+            let tail = self.tail();
+            self.len -= 1;
+            Some(tail)
+        } else {
+            None
+        }
+    }
+}
+
+/// One snake in the arena, a player's or a computer opponent's, and what it has
+/// earned this game.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Player {
+    pub body: SnakeBody,
+    pub dir: Dir,
+    /// Points from the food it has eaten.
+    pub score: usize,
+    /// Segments still to grow, one per move.
+    pub growth: usize,
+    /// False once a computer opponent has crashed out of the game.
+    pub alive: bool,
+    /// Power-ups running.
+    pub effects: Effects
+}
+
+impl Player {
+    pub fn new(start: Cell, dir: Dir, growth: usize) -> Self {
+        Self { body: SnakeBody::new(start), dir, score: 0, growth, alive: true, effects: Effects::default() }
+    }
+
+    pub fn is_growing(&self) -> bool {
+        self.growth != 0
+    }
+
+    /// The move under `rules` that leads from each segment to the one behind it, or
+    /// `None` where two segments don't touch.
+    pub(crate) fn steps<'a>(&'a self, rules: &'a GameSettings) -> impl Iterator<Item = Option<Dir>> + 'a {
+        self.body.iter().zip(self.body.iter().skip(1))
+            .map(|(a, b)| Dir::ALL.into_iter().find(|&d| rules.next_cell(a, d) == Some(b)))
+    }
+
+    /// The head, then the body as two-bit `steps`, four to a byte. Every step must
+    /// exist.
+    pub(crate) fn write_to(&self, w: &mut Writer, rules: &GameSettings) {
+        let head = self.body.head();
+        w.bytes(&[head.col, head.row]);
+        w.u16(self.body.len() as u16);
+        w.u8(self.dir as u8);
+        w.u32(self.score.min(u32::MAX as usize) as u32);
+        w.u16(self.growth.min(u16::MAX as usize) as u16);
+        w.u8(self.alive as u8);
+        self.effects.write_to(w);
+        let mut packed = 0;
+        for (i, step) in self.steps(rules).enumerate() {
+            packed |= (step.unwrap() as u8) << (2 * (i % 4));
+            if i % 4 == 3 {
+                w.u8(packed);
+                packed = 0;
+            }
+        }
+        if !(self.body.len() - 1).is_multiple_of(4) {
+            w.u8(packed);
+        }
+    }
+
+    pub(crate) fn read_from(r: &mut Reader, rules: &GameSettings) -> Option<Self> {
+        let [col, row] = r.bytes()?;
+        let head = Cell { col, row };
+        let len = r.u16()? as usize;
+        let dir = *Dir::ALL.get(r.u8()? as usize)?;
+        let (score, growth) = (r.u32()? as usize, r.u16()? as usize);
+        let alive = match r.u8()? {
+            0 => false,
+            1 => true,
+            _ => return None
+        };
+        let effects = Effects::read_from(r)?;
+        if !rules.in_playfield(head) || !(1..=MAX_LENGTH).contains(&len) {
+            return None;
+        }
+        let mut player = Self { body: SnakeBody::new(head), dir, score, growth, alive, effects };
+        let mut cell = head;
+        let mut packed = 0;
+        for i in 0..len - 1 {
+            if i % 4 == 0 {
+                packed = r.u8()?;
+            }
+            cell = rules.next_cell(cell, Dir::ALL[(packed >> (2 * (i % 4)) & 3) as usize])?;
+            player.body.push_tail(cell);
+        }
+        Some(player)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer_wraps_around() {
+        let mut body = SnakeBody::new(Cell::new(1, 2));
+        body.push_head(Cell::new(2, 2));
+        for i in 0..MAX_LENGTH + 10 {
+            body.push_head(Cell::new(i % 70 + 3, 2));
+            body.pop_tail();
+        }
+        assert_eq!(body.len(), 2);
+        assert_eq!(body.head(), Cell::new((MAX_LENGTH + 9) % 70 + 3, 2));
+        assert_eq!(body.tail(), Cell::new((MAX_LENGTH + 8) % 70 + 3, 2));
+    }
+
+    #[test]
+    fn pop_tail_keeps_the_head() {
+        let mut body = SnakeBody::new(Cell::new(1, 2));
+        assert_eq!(body.pop_tail(), None);
+        assert_eq!(body.len(), 1);
+    }
+
+    #[test]
+    fn dir_to_sees_across_the_wrap() {
+        assert_eq!(Cell::new(0, 3).dir_to(Cell::new(BUFFER_WIDTH - 1, 3)), Some(Dir::Left));
+        assert_eq!(Cell::new(4, 3).dir_to(Cell::new(6, 3)), None);
+    }
+}
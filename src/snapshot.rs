@@ -0,0 +1,367 @@
+//! Save states: everything a game in progress needs to carry on, packed into a blob
+//! that `SnakeDriver::restore` can pick up from later.
+//!
+//! The blob is the bytes `SNAP`, a version byte, the payload length and the payload,
+//! then a CRC-32 of everything before it. The payload holds the rules, the seed, the
+//! `Rng` state, the clocks, player 1's stats, the food and then each snake. A body is
+//! stored as its head and a two-bit step to each following segment, so a long snake
+//! takes a quarter of a byte per segment.
+
+use core::fmt;
+
+use crate::disk::crc32;
+use crate::death::DeathScene;
+use crate::food::Foods;
+use crate::pause::PauseMenu;
+use crate::power::Power;
+use crate::render::Renderer;
+use crate::replay::Recording;
+use crate::rng::Rng;
+use crate::settings::{GameSettings, MAX_SNAKES};
+use crate::snake::{Player, MAX_LENGTH};
+use crate::stats::RunStats;
+use crate::{SnakeDriver, Status};
+
+const MAGIC: &[u8; 4] = b"SNAP";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 7;
+const GAME_LEN: usize = GameSettings::ENCODED_LEN + 8 + 8 + 4 + 2 + 2 + 1 + RunStats::ENCODED_LEN + Foods::ENCODED_LEN + 1;
+/// A snake with its steps left out.
+const PLAYER_LEN: usize = 2 + 2 + 1 + 4 + 2 + 1 + 2 * Power::ALL.len();
+const MAX_PAYLOAD: usize = GAME_LEN + MAX_SNAKES * (PLAYER_LEN + (MAX_LENGTH - 1).div_ceil(4));
+/// The longest a blob can be: every snake on the board at full length.
+pub const MAX_SNAPSHOT_LEN: usize = HEADER_LEN + MAX_PAYLOAD + 4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SnapshotError {
+    /// The bytes don't start like a snapshot.
+    NotASnapshot,
+    /// Taken by a build with a different layout.
+    WrongVersion(u8),
+    BadChecksum,
+    /// The checksum matched but a field is out of range.
+    BadValue
+}
+
+impl SnapshotError {
+    /// A short description that fits on the HUD.
+    pub fn message(&self) -> &'static str {
+        match self {
+            SnapshotError::NotASnapshot => "not a save",
+            SnapshotError::WrongVersion(_) => "save from old version",
+            SnapshotError::BadChecksum => "save damaged",
+            SnapshotError::BadValue => "save unreadable"
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::WrongVersion(v) => write!(f, "taken by version {}, expected {}", v, VERSION),
+            _ => write!(f, "{}", self.message())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}
+
+/// Fills a payload front to back. Writing past the end is a bug in the length sums
+/// above, and panics.
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    at: usize
+}
+
+impl Writer<'_> {
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.at..self.at + bytes.len()].copy_from_slice(bytes);
+        self.at += bytes.len();
+    }
+
+    pub fn u8(&mut self, n: u8) {
+        self.bytes(&[n]);
+    }
+
+    pub fn u16(&mut self, n: u16) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, n: u32) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, n: u64) {
+        self.bytes(&n.to_le_bytes());
+    }
+}
+
+/// Reads a payload front to back; `None` once it runs out.
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8]
+}
+
+impl Reader<'_> {
+    pub fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.buf.split_first_chunk::<N>()?;
+        self.buf = rest;
+        Some(*bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[b]| b)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+}
+
+/// A game frozen at one moment.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Snapshot {
+    bytes: [u8; MAX_SNAPSHOT_LEN],
+    len: usize
+}
+
+impl Snapshot {
+    /// Seals the payload `fill` writes with the header and checksum.
+    fn build(fill: impl FnOnce(&mut Writer)) -> Self {
+        let mut bytes = [0; MAX_SNAPSHOT_LEN];
+        let mut w = Writer { buf: &mut bytes[HEADER_LEN..MAX_SNAPSHOT_LEN - 4], at: 0 };
+        fill(&mut w);
+        let payload = w.at;
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = VERSION;
+        bytes[5..7].copy_from_slice(&(payload as u16).to_le_bytes());
+        let len = HEADER_LEN + payload + 4;
+        let crc = crc32(&bytes[..len - 4]);
+        bytes[len - 4..len].copy_from_slice(&crc.to_le_bytes());
+        Self { bytes, len }
+    }
+
+    /// Checks a blob and keeps a copy of it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < HEADER_LEN + 4 || &bytes[..4] != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        if bytes[4] != VERSION {
+            return Err(SnapshotError::WrongVersion(bytes[4]));
+        }
+        let len = HEADER_LEN + u16::from_le_bytes([bytes[5], bytes[6]]) as usize + 4;
+        if bytes.len() != len || len > MAX_SNAPSHOT_LEN {
+            return Err(SnapshotError::BadValue);
+        }
+        if crc32(&bytes[..len - 4]).to_le_bytes() != bytes[len - 4..] {
+            return Err(SnapshotError::BadChecksum);
+        }
+        let mut snapshot = Self { bytes: [0; MAX_SNAPSHOT_LEN], len };
+        snapshot.bytes[..len].copy_from_slice(bytes);
+        Ok(snapshot)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// A reader over the payload.
+    fn payload(&self) -> Reader<'_> {
+        Reader { buf: &self.bytes[HEADER_LEN..self.len - 4] }
+    }
+}
+
+/// The parts of `SnakeDriver` a snapshot holds, read back and checked before any of
+/// them replace the game on screen.
+struct Game {
+    rules: GameSettings,
+    seed: u64,
+    rng: Rng,
+    ticks: u32,
+    countdown: usize,
+    boost: usize,
+    winner: Option<usize>,
+    stats: RunStats,
+    food: Foods,
+    players: [Player; MAX_SNAKES]
+}
+
+impl Game {
+    fn read_from(r: &mut Reader, fallback: Player) -> Option<Self> {
+        let rules = GameSettings::decode(r.bytes()?)?;
+        let seed = r.u64()?;
+        let rng = Rng::from_state(r.u64()?);
+        let ticks = r.u32()?;
+        let countdown = r.u16()? as usize;
+        let boost = r.u16()? as usize;
+        let winner = match r.u8()? {
+            0xFF => None,
+            p if p < rules.players => Some(p as usize),
+            _ => return None
+        };
+        let stats = RunStats::read_from(r)?;
+        let food = Foods::read_from(r)?;
+        if r.u8()? as usize != rules.snakes() {
+            return None;
+        }
+        let mut players = [fallback; MAX_SNAKES];
+        for player in players.iter_mut().take(rules.snakes()) {
+            *player = Player::read_from(r, &rules)?;
+        }
+        Some(Self { rules, seed, rng, ticks, countdown, boost, winner, stats, food, players })
+    }
+}
+
+impl<R: Renderer> SnakeDriver<R> {
+    /// The game in progress, or `None` when there isn't one to save: outside a game,
+    /// during a crash, or while a demo or replay is showing.
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        if !matches!(self.status, Status::GameOn | Status::Paused | Status::Resuming) || self.demo || self.playback.is_some() {
+            return None;
+        }
+        let rules = self.rules;
+        let players = self.players();
+        if players.iter().any(|p| p.steps(&rules).any(|step| step.is_none())) {
+            return None;
+        }
+        Some(Snapshot::build(|w| {
+            w.bytes(&rules.encode());
+            w.u64(self.seed);
+            w.u64(self.rng.state());
+            w.u32(self.ticks);
+            w.u16(self.countdown.min(u16::MAX as usize) as u16);
+            w.u16(self.boost.min(u16::MAX as usize) as u16);
+            w.u8(self.winner.map_or(0xFF, |p| p as u8));
+            self.stats.write_to(w);
+            self.food.write_to(w);
+            w.u8(players.len() as u8);
+            for player in players {
+                player.write_to(w, &rules);
+            }
+        }))
+    }
+
+    /// Puts the game back as it was when `snapshot` was taken, running, and redraws
+    /// it. A blob that doesn't check out leaves everything alone. The restored game
+    /// can't be replayed, since its recording would start partway through, and doesn't
+    /// go on the high score table.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let mut r = snapshot.payload();
+        let game = Game::read_from(&mut r, self.players[0]).ok_or(SnapshotError::BadValue)?;
+        if r.u8().is_some() {
+            return Err(SnapshotError::BadValue);
+        }
+        self.apply_rules(game.rules);
+        self.init_game(game.seed);
+        self.recording = Recording::new(game.seed, self.rules);
+        self.recording.mark_incomplete();
+        self.playback = None;
+        self.demo = false;
+        self.loaded = true;
+        self.rng = game.rng;
+        self.ticks = game.ticks;
+        self.countdown = game.countdown;
+        self.boost = game.boost;
+        self.winner = game.winner;
+        self.stats = game.stats;
+        self.food = game.food;
+        self.players = game.players;
+        self.best_rank = None;
+        self.death = DeathScene::default();
+        self.pause = PauseMenu::default();
+        self.redraw();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::food::{Food, FoodKind};
+    use crate::power::Effects;
+    use crate::render::Grid;
+    use crate::settings::WallMode;
+    use crate::snake::{Cell, Dir};
+
+    /// The snakes as they play, leaving out the unused part of each body's buffer.
+    fn snakes(d: &SnakeDriver<Grid>) -> Vec<(Vec<Cell>, Dir, usize, usize, bool, Effects)> {
+        d.players().iter().map(|p| (p.body.iter().collect(), p.dir, p.score, p.growth, p.alive, p.effects)).collect()
+    }
+
+    fn game(walls: WallMode) -> SnakeDriver<Grid> {
+        let settings = GameSettings { walls, players: 2, ..GameSettings::default() };
+        let mut d = SnakeDriver::with_settings(Grid::default(), 7, settings);
+        d.redraw();
+        for _ in 0..30 {
+            d.tick();
+        }
+        assert_eq!(d.status(), Status::GameOn);
+        d
+    }
+
+    #[test]
+    fn a_restored_game_plays_on_exactly_like_the_original() {
+        for walls in WallMode::ALL {
+            let mut a = game(walls);
+            a.players[0].effects.grant(Power::Magnet);
+            let snapshot = a.snapshot().unwrap();
+            assert!(snapshot.as_bytes().len() < 200);
+            let mut b = SnakeDriver::with_seed(Grid::default(), 99);
+            b.restore(&Snapshot::from_bytes(snapshot.as_bytes()).unwrap()).unwrap();
+            assert_eq!((snakes(&b), b.food, b.stats, b.rng), (snakes(&a), a.food, a.stats, a.rng));
+            a.redraw();
+            assert_eq!(b.renderer(), a.renderer());
+            for _ in 0..300 {
+                a.tick();
+                b.tick();
+            }
+            assert_eq!((snakes(&b), b.food, b.status), (snakes(&a), a.food, a.status), "{walls:?}");
+            assert!(!b.recording().is_complete());
+        }
+    }
+
+    #[test]
+    fn bodies_across_the_wrap_pack_into_steps() {
+        let mut d = game(WallMode::Wrap);
+        let (right, row) = (d.rules.width as usize - 2, 5);
+        let mut player = Player::new(Cell::new(right, row), Dir::Right, 0);
+        player.body.push_head(Cell::new(1, row));
+        player.body.push_head(Cell::new(1, row - 1));
+        d.players[0] = player;
+        d.food.clear();
+        d.food.add(Food::new(Cell::new(3, 3), FoodKind::Golden));
+        let snapshot = d.snapshot().unwrap();
+        let mut e = SnakeDriver::with_seed(Grid::default(), 1);
+        e.restore(&snapshot).unwrap();
+        assert_eq!(snakes(&e), snakes(&d));
+        assert_eq!(e.food, d.food);
+    }
+
+    #[test]
+    fn damaged_or_foreign_blobs_are_turned_away() {
+        let snapshot = game(WallMode::Solid).snapshot().unwrap();
+        let bytes = snapshot.as_bytes();
+        assert_eq!(Snapshot::from_bytes(&bytes[..3]), Err(SnapshotError::NotASnapshot));
+        let mut copy = bytes.to_vec();
+        copy[4] = 9;
+        assert_eq!(Snapshot::from_bytes(&copy), Err(SnapshotError::WrongVersion(9)));
+        let mut copy = bytes.to_vec();
+        copy[HEADER_LEN + 20] ^= 1;
+        assert_eq!(Snapshot::from_bytes(&copy), Err(SnapshotError::BadChecksum));
+        assert_eq!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]), Err(SnapshotError::BadValue));
+
+        let mut d = SnakeDriver::with_seed(Grid::default(), 5);
+        let before = d.players;
+        let bad = Snapshot::build(|w| w.bytes(&[0; GAME_LEN]));
+        assert_eq!(d.restore(&bad), Err(SnapshotError::BadValue));
+        assert_eq!(d.players, before);
+    }
+}
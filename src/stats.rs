@@ -0,0 +1,138 @@
+//! What happened over one game, for the results panel at the end of it.
+
+use crate::render::{Renderer, BUFFER_WIDTH};
+use crate::snapshot::{Reader, Writer};
+use crate::ui::{draw_box, draw_str, Align, Digits, Frame, Rect};
+use crate::Pal;
+
+/// Timer ticks in ten seconds; the PIT fires about 18.2 times a second.
+const TICKS_PER_10S: u64 = 182;
+
+/// What a snake ran into.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Cause {
+    Wall,
+    OwnBody,
+    /// Another snake's body, or its head on the same move.
+    Opponent,
+    Poison
+}
+
+impl Cause {
+    const ALL: [Cause; 4] = [Cause::Wall, Cause::OwnBody, Cause::Opponent, Cause::Poison];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Cause::Wall => "a wall",
+            Cause::OwnBody => "its own body",
+            Cause::Opponent => "another snake",
+            Cause::Poison => "poison"
+        }
+    }
+}
+
+/// Player 1's running totals for the current game.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct RunStats {
+    /// Timer ticks spent playing, not counting pauses.
+    pub ticks: u32,
+    pub moves: u32,
+    /// Changes of heading.
+    pub turns: u32,
+    /// Food eaten that made the snake grow.
+    pub apples: u32,
+    pub max_length: usize,
+    /// What ended the game, if it has ended.
+    pub cause: Option<Cause>
+}
+
+impl RunStats {
+    pub(crate) const ENCODED_LEN: usize = 4 * 4 + 2 + 1;
+
+    pub fn seconds(&self) -> u64 {
+        self.ticks as u64 * 10 / TICKS_PER_10S
+    }
+
+    pub fn turns_per_minute(&self) -> u64 {
+        if self.ticks == 0 {
+            return 0;
+        }
+        self.turns as u64 * TICKS_PER_10S * 6 / self.ticks as u64
+    }
+
+    /// Draws the results panel over the middle of the board. `rank` is where the game
+    /// landed on the high score table, if it did.
+    pub fn draw<R: Renderer>(&self, score: usize, seed: u64, rank: Option<usize>, can_replay: bool, r: &mut R) {
+        let text = Pal::disp(Pal::Text);
+        let panel = Rect::new(BUFFER_WIDTH / 2 - 18, 5, 36, 15);
+        let (left, top) = (panel.left, panel.top);
+        draw_box(r, panel, Frame::Double, text);
+        draw_str(r, "GAME OVER", panel.middle(), top + 1, Align::Center, text);
+        match rank {
+            Some(0) => { draw_str(r, "NEW HIGH SCORE!", panel.middle(), top + 2, Align::Center, text); }
+            Some(rank) => {
+                let end = r.plot_str("High score table: #", left + 7, top + 2, text);
+                r.plot_num(rank as isize + 1, end, top + 2, text);
+            }
+            None => {}
+        }
+
+        let (label, value) = (left + 3, left + 19);
+        let numbers = [
+            ("Score", score as u64),
+            ("Longest", self.max_length as u64),
+            ("Apples eaten", self.apples as u64),
+            ("Moves", self.moves as u64),
+            ("Turns/min", self.turns_per_minute())
+        ];
+        for (i, (name, n)) in numbers.into_iter().enumerate() {
+            r.plot_str(name, label, top + 4 + i, text);
+            r.plot_num(n as isize, value, top + 4 + i, text);
+        }
+        let row = top + 4 + numbers.len();
+        r.plot_str("Time", label, row, text);
+        let (minutes, seconds) = (self.seconds() / 60, self.seconds() % 60);
+        let end = r.plot_num(minutes as isize, value, row, text);
+        let end = r.plot_str(":", end, row, text);
+        r.plot_str(Digits::new(seconds).pad(2, b'0').as_str(), end, row, text);
+        r.plot_str("Killed by", label, row + 1, text);
+        r.plot_str(self.cause.map_or("-", Cause::name), value, row + 1, text);
+        r.plot_str("Seed", label, row + 2, text);
+        for i in 0..16 {
+            let nibble = (seed >> (60 - 4 * i) & 0xF) as u32;
+            r.plot(char::from_digit(nibble, 16).unwrap().to_ascii_uppercase(), value + i, row + 2, text);
+        }
+
+        let options = if can_replay { "R: retry  V: replay  Esc: title" } else { "R: retry  Esc: title" };
+        draw_str(r, options, panel.middle(), top + 13, Align::Center, text);
+    }
+
+    pub(crate) fn write_to(&self, w: &mut Writer) {
+        [self.ticks, self.moves, self.turns, self.apples].into_iter().for_each(|n| w.u32(n));
+        w.u16(self.max_length.min(u16::MAX as usize) as u16);
+        w.u8(self.cause.map_or(0, |cause| cause as u8 + 1));
+    }
+
+    pub(crate) fn read_from(r: &mut Reader) -> Option<Self> {
+        let [ticks, moves, turns, apples] = [r.u32()?, r.u32()?, r.u32()?, r.u32()?];
+        let max_length = r.u16()? as usize;
+        let cause = match r.u8()? {
+            0 => None,
+            n => Some(*Cause::ALL.get(n as usize - 1)?)
+        };
+        Some(Self { ticks, moves, turns, apples, max_length, cause })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_and_pace_come_from_timer_ticks() {
+        let stats = RunStats { ticks: 1092, turns: 30, ..RunStats::default() };
+        assert_eq!(stats.seconds(), 60);
+        assert_eq!(stats.turns_per_minute(), 30);
+        assert_eq!(RunStats::default().turns_per_minute(), 0);
+    }
+}
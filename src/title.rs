@@ -0,0 +1,142 @@
+use crate::render::{Renderer, BUFFER_WIDTH};
+use crate::settings::{GameSettings, MAX_OPPONENTS, MAX_PLAYERS};
+use crate::snake::Dir;
+use crate::ui::{draw_str, Align};
+use crate::Pal;
+
+/// The entries of the title menu, top to bottom.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MenuItem {
+    Play,
+    /// Cycles the wall mode with Left/Right.
+    Mode,
+    /// Picks one or two players, and how many computer snakes join them, with
+    /// Left/Right.
+    Players,
+    Options,
+    HighScores,
+    Replay,
+    /// Stops the kernel.
+    Quit
+}
+
+const ITEMS: [MenuItem; 7] = [
+    MenuItem::Play, MenuItem::Mode, MenuItem::Players, MenuItem::Options,
+    MenuItem::HighScores, MenuItem::Replay, MenuItem::Quit
+];
+
+impl MenuItem {
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::Play => "Play",
+            MenuItem::Mode => "Mode",
+            MenuItem::Players => "Players",
+            MenuItem::Options => "Options",
+            MenuItem::HighScores => "High Scores",
+            MenuItem::Replay => "Replay",
+            MenuItem::Quit => "Quit"
+        }
+    }
+}
+
+const TITLE: &str = "A  HASTY  SNAKE  CLONE";
+const TITLE_ROW: usize = 4;
+const MENU_COL: usize = 34;
+const MENU_ROW: usize = 9;
+const HINT: &str = "Arrows: choose  Enter: select";
+
+/// Steps through every line-up: one player, then two, then both again with each
+/// extra computer snake.
+fn cycle_lineup(settings: &mut GameSettings, up: bool) {
+    let players = MAX_PLAYERS as usize;
+    let count = players * (MAX_OPPONENTS as usize + 1);
+    let i = settings.opponents as usize * players + settings.players as usize - 1;
+    let i = (i + if up { 1 } else { count - 1 }) % count;
+    settings.players = (i % players) as u8 + 1;
+    settings.opponents = (i / players) as u8;
+}
+
+/// The title screen: Up/Down picks an entry and Enter takes it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct TitleMenu {
+    selected: usize
+}
+
+impl TitleMenu {
+    pub fn selected(&self) -> MenuItem {
+        ITEMS[self.selected]
+    }
+
+    pub fn handle(&mut self, dir: Dir, settings: &mut GameSettings) {
+        match (dir, self.selected()) {
+            (Dir::Up, _) => self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len(),
+            (Dir::Down, _) => self.selected = (self.selected + 1) % ITEMS.len(),
+            (Dir::Left, MenuItem::Mode) => settings.walls = settings.walls.cycle(false),
+            (Dir::Right, MenuItem::Mode) => settings.walls = settings.walls.cycle(true),
+            (Dir::Left, MenuItem::Players) => cycle_lineup(settings, false),
+            (Dir::Right, MenuItem::Players) => cycle_lineup(settings, true),
+            _ => {}
+        }
+    }
+
+    /// `can_replay` says whether there is a finished game to show again.
+    pub fn draw<R: Renderer>(&self, settings: &GameSettings, can_replay: bool, r: &mut R) {
+        r.clear_screen();
+        let text = Pal::disp(Pal::Text);
+        draw_str(r, TITLE, BUFFER_WIDTH / 2, TITLE_ROW, Align::Center, text);
+        for (i, item) in ITEMS.iter().enumerate() {
+            let row = MENU_ROW + 2 * i;
+            r.plot(if i == self.selected { '>' } else { ' ' }, MENU_COL - 2, row, text);
+            let end = r.plot_str(item.label(), MENU_COL, row, text);
+            match item {
+                MenuItem::Mode => {
+                    let end = r.plot_str(": < ", end, row, text);
+                    let end = r.plot_str(settings.walls.name(), end, row, text);
+                    r.plot_str(" >", end, row, text);
+                }
+                MenuItem::Players => {
+                    let end = r.plot_str(": < ", end, row, text);
+                    let mut end = r.plot_num(settings.players as isize, end, row, text);
+                    if settings.opponents > 0 {
+                        end = r.plot_str(" + ", end, row, text);
+                        end = r.plot_num(settings.opponents as isize, end, row, text);
+                        end = r.plot_str(" CPU", end, row, text);
+                    }
+                    r.plot_str(" >", end, row, text);
+                }
+                MenuItem::Replay if !can_replay => { r.plot_str(" (none yet)", end, row, text); }
+                _ => {}
+            }
+        }
+        draw_str(r, HINT, BUFFER_WIDTH / 2, MENU_ROW + 2 * ITEMS.len() + 1, Align::Center, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::WallMode;
+
+    #[test]
+    fn selection_wraps_and_mode_cycles_walls() {
+        let mut menu = TitleMenu::default();
+        let mut settings = GameSettings::default();
+        menu.handle(Dir::Up, &mut settings);
+        assert_eq!(menu.selected(), MenuItem::Quit);
+        menu.handle(Dir::Right, &mut settings);
+        assert_eq!(settings.walls, WallMode::Solid);
+        menu.handle(Dir::Down, &mut settings);
+        menu.handle(Dir::Down, &mut settings);
+        assert_eq!(menu.selected(), MenuItem::Mode);
+        menu.handle(Dir::Right, &mut settings);
+        assert_eq!(settings.walls, WallMode::Wrap);
+        menu.handle(Dir::Down, &mut settings);
+        menu.handle(Dir::Left, &mut settings);
+        assert_eq!((settings.players, settings.opponents), (2, 2));
+        menu.handle(Dir::Right, &mut settings);
+        menu.handle(Dir::Right, &mut settings);
+        assert_eq!((settings.players, settings.opponents), (2, 0));
+        menu.handle(Dir::Right, &mut settings);
+        assert_eq!((settings.players, settings.opponents), (1, 1));
+    }
+}
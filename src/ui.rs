@@ -0,0 +1,299 @@
+//! Text and panels built on `Renderer::plot`: aligned strings, padded numbers, framed
+//! boxes, menu lists and dialogs that put back whatever they covered.
+//!
+//! Box borders use code page 437 line characters. `Renderer` takes a `char` and the
+//! VGA buffer keeps its low byte, so they are written as the CP437 byte values.
+
+use crate::render::{ColorCode, Renderer, BUFFER_HEIGHT, BUFFER_WIDTH};
+use crate::Pal;
+
+/// The biggest area a dialog can cover.
+pub const DIALOG_MAX_WIDTH: usize = 48;
+pub const DIALOG_MAX_HEIGHT: usize = 16;
+
+/// Where a string sits relative to the column it is drawn at.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Align {
+    /// Starts at the column.
+    Left,
+    /// Straddles the column, with any odd character to the left of it.
+    Center,
+    /// Ends just before the column.
+    Right
+}
+
+/// Draws `s` on `row` placed against `col` by `align`, cutting it off at the screen's
+/// edges. Returns the column after the last character.
+pub fn draw_str<R: Renderer>(r: &mut R, s: &str, col: usize, row: usize, align: Align, color: ColorCode) -> usize {
+    let len = s.chars().count();
+    let start = match align {
+        Align::Left => col as isize,
+        Align::Center => col as isize - len.div_ceil(2) as isize,
+        Align::Right => col as isize - len as isize
+    };
+    let mut end = start.max(0) as usize;
+    for (c, chr) in (start..).zip(s.chars()) {
+        if (0..BUFFER_WIDTH as isize).contains(&c) {
+            r.plot(chr, c as usize, row, color);
+            end = c as usize + 1;
+        }
+    }
+    end
+}
+
+/// Blanks `row` from `from` up to, but not including, `to`.
+pub fn fill<R: Renderer>(r: &mut R, from: usize, to: usize, row: usize, color: ColorCode) {
+    for col in from..to.min(BUFFER_WIDTH) {
+        r.plot(' ', col, row, color);
+    }
+}
+
+/// A number written out in decimal, so it can be measured and aligned like any string.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Digits {
+    buf: [u8; 20],
+    start: usize
+}
+
+impl Digits {
+    pub fn new(n: u64) -> Self {
+        let mut digits = Self { buf: [b' '; 20], start: 20 };
+        let mut n = n;
+        loop {
+            digits.start -= 1;
+            digits.buf[digits.start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 { break; }
+        }
+        digits
+    }
+
+    /// `n` in at most `width` digits; numbers too big for them show as all nines.
+    pub fn capped(n: u64, width: usize) -> Self {
+        Self::new(n.min(10u64.saturating_pow(width as u32) - 1))
+    }
+
+    /// Pads on the left with `fill` out to `width` characters.
+    pub fn pad(mut self, width: usize, fill: u8) -> Self {
+        while self.buf.len() - self.start < width.min(self.buf.len()) {
+            self.start -= 1;
+            self.buf[self.start] = fill;
+        }
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[self.start..]).unwrap_or("")
+    }
+}
+
+/// A block of screen cells.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rect {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Rect {
+    pub const fn new(left: usize, top: usize, width: usize, height: usize) -> Self {
+        Self { left, top, width, height }
+    }
+
+    /// A `width` by `height` block in the middle of the screen.
+    pub const fn centered(width: usize, height: usize) -> Self {
+        Self::new((BUFFER_WIDTH - width) / 2, (BUFFER_HEIGHT - height) / 2, width, height)
+    }
+
+    pub fn right(&self) -> usize {
+        self.left + self.width - 1
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.top + self.height - 1
+    }
+
+    /// The column in the middle, for `Align::Center`.
+    pub fn middle(&self) -> usize {
+        self.left + self.width / 2
+    }
+}
+
+/// The line style of a box's border.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Frame {
+    Single,
+    Double
+}
+
+impl Frame {
+    /// Top left, top right, bottom left, bottom right, across, down.
+    fn chars(self) -> [char; 6] {
+        match self {
+            Frame::Single => ['\u{DA}', '\u{BF}', '\u{C0}', '\u{D9}', '\u{C4}', '\u{B3}'],
+            Frame::Double => ['\u{C9}', '\u{BB}', '\u{C8}', '\u{BC}', '\u{CD}', '\u{BA}']
+        }
+    }
+}
+
+/// Draws a border around the edge of `rect` and blanks everything inside it.
+pub fn draw_box<R: Renderer>(r: &mut R, rect: Rect, frame: Frame, color: ColorCode) {
+    let [top_left, top_right, bottom_left, bottom_right, across, down] = frame.chars();
+    for row in rect.top..=rect.bottom() {
+        let (first, middle, last) = match row {
+            _ if row == rect.top => (top_left, across, top_right),
+            _ if row == rect.bottom() => (bottom_left, across, bottom_right),
+            _ => (down, ' ', down)
+        };
+        r.plot(first, rect.left, row, color);
+        for col in rect.left + 1..rect.right() {
+            r.plot(middle, col, row, color);
+        }
+        r.plot(last, rect.right(), row, color);
+    }
+}
+
+/// A list of labels, one per row, with a highlighted cursor on one of them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct MenuList {
+    selected: usize
+}
+
+impl MenuList {
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, i: usize) {
+        self.selected = i;
+    }
+
+    /// Moves the cursor a row down, or up, through `len` entries, wrapping at the ends.
+    pub fn step(&mut self, down: bool, len: usize) {
+        self.selected = (self.selected + if down { 1 } else { len - 1 }) % len;
+    }
+
+    /// Draws `labels` down from `top`, each on a bar `width` wide starting at `left`.
+    /// The selected one gets a marker and the highlight colour.
+    pub fn draw<R: Renderer>(&self, labels: &[&str], left: usize, top: usize, width: usize, r: &mut R) {
+        for (i, label) in labels.iter().enumerate() {
+            let (marker, color) = if i == self.selected {
+                ('>', Pal::disp(Pal::Highlight))
+            } else {
+                (' ', Pal::disp(Pal::Text))
+            };
+            r.plot(marker, left, top + i, color);
+            let end = draw_str(r, label, left + 2, top + i, Align::Left, color);
+            fill(r, end, left + width, top + i, color);
+        }
+    }
+}
+
+/// A framed box drawn over the screen that keeps a copy of the cells it covers, so
+/// closing it leaves the screen as it was.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Dialog {
+    rect: Rect,
+    under: [[(char, ColorCode); DIALOG_MAX_WIDTH]; DIALOG_MAX_HEIGHT]
+}
+
+impl Dialog {
+    /// Saves what is under `rect`, cut down to the largest dialog if need be, and draws
+    /// an empty box over it with `title` centred in the top border. A title too long for
+    /// the border is cut short, leaving a corner and a space at each end.
+    pub fn open<R: Renderer>(rect: Rect, title: &str, r: &mut R) -> Self {
+        let rect = Rect { width: rect.width.min(DIALOG_MAX_WIDTH), height: rect.height.min(DIALOG_MAX_HEIGHT), ..rect };
+        let mut under = [[(' ', Pal::disp(Pal::EmptySpace)); DIALOG_MAX_WIDTH]; DIALOG_MAX_HEIGHT];
+        for (row, saved) in (rect.top..=rect.bottom()).zip(under.iter_mut()) {
+            for (col, cell) in (rect.left..=rect.right()).zip(saved.iter_mut()) {
+                *cell = r.peek(col, row);
+            }
+        }
+        let text = Pal::disp(Pal::Text);
+        draw_box(r, rect, Frame::Double, text);
+        let room = rect.width.saturating_sub(4);
+        let title = title.char_indices().nth(room).map_or(title, |(i, _)| &title[..i]);
+        if !title.is_empty() {
+            let end = draw_str(r, title, rect.middle(), rect.top, Align::Center, text);
+            let start = end.saturating_sub(title.chars().count());
+            if start > rect.left {
+                r.plot(' ', start - 1, rect.top, text);
+            }
+            if end < rect.right() {
+                r.plot(' ', end, rect.top, text);
+            }
+        }
+        Self { rect, under }
+    }
+
+    /// The area the dialog covers, border included.
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    /// Puts back the cells the dialog covered.
+    pub fn close<R: Renderer>(self, r: &mut R) {
+        for (row, saved) in (self.rect.top..=self.rect.bottom()).zip(self.under.iter()) {
+            for (col, &(c, color)) in (self.rect.left..=self.rect.right()).zip(saved.iter()) {
+                r.plot(c, col, row, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Grid;
+
+    #[test]
+    fn strings_line_up_against_their_column() {
+        let mut grid = Grid::default();
+        let text = Pal::disp(Pal::Text);
+        assert_eq!(draw_str(&mut grid, "abc", 10, 1, Align::Right, text), 10);
+        assert!(grid.reads(7, 1, "abc"));
+        assert_eq!(draw_str(&mut grid, "GAME OVER", 40, 2, Align::Center, text), 44);
+        assert!(grid.reads(35, 2, "GAME OVER"));
+        draw_str(&mut grid, "xyz", 1, 3, Align::Right, text);
+        assert!(grid.reads(0, 3, "z"));
+        assert_eq!(Digits::new(0).as_str(), "0");
+        assert_eq!(Digits::new(42).pad(5, b'0').as_str(), "00042");
+        assert_eq!(Digits::capped(123456, 4).pad(6, b' ').as_str(), "  9999");
+    }
+
+    #[test]
+    fn dialogs_put_back_what_they_covered() {
+        let mut grid = Grid::default();
+        grid.plot_str("the board under it", 20, 10, Pal::disp(Pal::Snake));
+        let before = grid;
+        let dialog = Dialog::open(Rect::centered(20, 5), "PAUSED", &mut grid);
+        assert_eq!(dialog.rect(), Rect::new(30, 10, 20, 5));
+        assert_eq!(grid.char_at(30, 10), '\u{C9}');
+        assert_eq!(grid.char_at(49, 14), '\u{BC}');
+        assert!(grid.reads(36, 10, " PAUSED "));
+        assert_eq!(grid.char_at(35, 11), ' ');
+        dialog.close(&mut grid);
+        assert_eq!(grid, before);
+        let dialog = Dialog::open(Rect::new(0, 0, 10, 3), "A TITLE FAR TOO LONG", &mut grid);
+        assert!(grid.reads(1, 0, " A TITL "));
+        assert_eq!((grid.char_at(0, 0), grid.char_at(9, 0)), ('\u{C9}', '\u{BB}'));
+        dialog.close(&mut grid);
+        assert_eq!(grid, before);
+    }
+
+    #[test]
+    fn the_menu_cursor_wraps_and_is_highlighted() {
+        let mut menu = MenuList::default();
+        menu.step(false, 3);
+        assert_eq!(menu.selected(), 2);
+        menu.step(true, 3);
+        assert_eq!(menu.selected(), 0);
+        menu.select(1);
+        let mut grid = Grid::default();
+        menu.draw(&["Resume", "Quit"], 5, 2, 10, &mut grid);
+        assert!(grid.reads(5, 3, "> Quit    "));
+        assert_eq!(grid.color_at(14, 3), Pal::disp(Pal::Highlight));
+        assert_eq!(grid.color_at(5, 2), Pal::disp(Pal::Text));
+    }
+}